@@ -0,0 +1,836 @@
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use chrono::{Local, TimeZone};
+use rusqlite::Connection;
+use serde::Serialize;
+use uuid::Uuid;
+use std::error::Error;
+
+use rust_trabalho::ledger;
+use rust_trabalho::PollError;
+use rust_trabalho::poll;
+use rust_trabalho::poll::{Outcome, Poll, PollKind, PollResults, PollRevision, PollStatus};
+use rust_trabalho::server;
+use rust_trabalho::permission::{self, Action};
+use rust_trabalho::user::{self, Role, User};
+use rust_trabalho::vote;
+use rust_trabalho::vote::{Vote, VoteEvent};
+use rust_trabalho::voter;
+
+/// Create polls and vote on them. Without a command the interactive menu is opened.
+#[derive(Parser, Debug)]
+#[command(version)]
+pub struct Cli {
+    /// SQLite database file
+    #[arg(long, global = true, default_value = "database.db")]
+    pub database: String,
+
+    /// How `results`, `audit`, `poll list`, `poll history`, `vote list`, `vote check`, `voter list` and `user list` are printed
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Log in as this user, which is needed for anything but reading. The password is asked for,
+    /// or read from RUST_TRABALHO_PASSWORD
+    #[arg(long, global = true)]
+    pub user: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Text,
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Create and list polls
+    Poll {
+        #[command(subcommand)]
+        command: PollCommand,
+    },
+    /// Cast and list votes
+    Vote {
+        #[command(subcommand)]
+        command: VoteCommand,
+    },
+    /// Set voter weights and list voters
+    Voter {
+        #[command(subcommand)]
+        command: VoterCommand,
+    },
+    /// Create accounts and list them
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Show the results of a poll, or of every poll when no id is given
+    Results {
+        /// Poll id or short code, as shown by `poll list`
+        poll: Option<String>,
+    },
+    /// Show every vote cast, changed or retracted on a poll or by a voter
+    #[command(group(ArgGroup::new("subject").required(true).args(["poll", "voter"])))]
+    Audit {
+        /// Poll id or short code. Deleted polls can still be given by their full id
+        #[arg(long)]
+        poll: Option<String>,
+        /// Name of the voter
+        #[arg(long)]
+        voter: Option<String>,
+    },
+    /// Check the vote counters against the votes
+    Verify {
+        /// Rewrite the counters that don't match
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Check the vote log's hash chain, and the votes against it. Anyone who can write to the database can
+    /// also rehash the log, so keep the head it prints somewhere else and pass it back with --head
+    VerifyLedger {
+        /// Head printed by an earlier check, which the log has to still have
+        #[arg(long)]
+        head: Option<String>,
+    },
+    /// Serve the REST API over HTTP. It only takes sign-ups once the first account, the admin, was created
+    /// with `user create`
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        addr: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PollCommand {
+    /// Create a poll
+    #[command(group(ArgGroup::new("length").required(true).args(["days", "duration"])))]
+    Create {
+        #[arg(long)]
+        question: String,
+        /// Number of days the poll stays open
+        #[arg(long)]
+        days: Option<u32>,
+        /// Hours (36h), days (3d) or an end date (2024-12-31)
+        #[arg(long)]
+        duration: Option<String>,
+        /// An option voters can choose, repeat it for each one. Defaults to Yes and No
+        #[arg(long = "option")]
+        options: Vec<String>,
+        /// When voting starts (2024-12-31 or 2024-12-31 18:00). Defaults to now
+        #[arg(long, default_value = "")]
+        start: String,
+        /// Least turnout, abstentions included, for the result to count
+        #[arg(long, default_value_t = 0)]
+        quorum: i64,
+        /// Share of the votes the leading option needs to pass: majority, a fraction (2/3) or a percentage (60%)
+        #[arg(long, default_value = "majority")]
+        threshold: String,
+        /// single, ranked for voters to rank the options (instant runoff), approval to pick any number of them
+        /// or score to rate each one from 0 to 5
+        #[arg(long, default_value = "single")]
+        kind: String,
+        /// Keep votes apart from their voters, who are only recorded as having voted. Every vote counts once,
+        /// whatever the voter's weight. Votes can only be changed or retracted with the receipt given when voting
+        #[arg(long)]
+        anonymous: bool,
+    },
+    /// List all polls
+    List,
+    /// Show how a poll was edited since it was created
+    History {
+        /// Poll id or short code, as shown by `poll list`
+        #[arg(long)]
+        poll: String,
+    },
+    /// Close a poll before its expiration date
+    Close {
+        /// Poll id or short code, as shown by `poll list`
+        #[arg(long)]
+        poll: String,
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// Open a closed poll again, or a draft for the first time
+    Reopen {
+        #[arg(long)]
+        poll: String,
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// Turn an open poll nobody voted on back into a draft
+    Draft {
+        #[arg(long)]
+        poll: String,
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+    /// Archive a closed poll for good
+    Archive {
+        #[arg(long)]
+        poll: String,
+        #[arg(long, default_value = "")]
+        reason: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VoteCommand {
+    /// Vote on a poll as the logged in user
+    Cast {
+        /// Poll id or short code, as shown by `poll list`
+        #[arg(long)]
+        poll: String,
+        /// Option label or number, or "abstain". On ranked polls the options in order, like "2,1,3",
+        /// on approval polls the approved options, like "1,3", and on score polls a score per option, like "5,0,3"
+        #[arg(long)]
+        choice: String,
+        #[arg(long, default_value = "")]
+        comment: String,
+    },
+    /// Show the vote a receipt was handed out for, to check it was counted
+    Check {
+        /// Receipt printed when the vote was cast
+        receipt: String,
+    },
+    /// Change the vote a receipt was handed out for
+    Change {
+        /// Receipt printed when the vote was cast
+        receipt: String,
+        /// New choice, written the same way as for `vote cast`
+        #[arg(long)]
+        choice: String,
+        /// New comment. The old one is kept when left empty
+        #[arg(long, default_value = "")]
+        comment: String,
+    },
+    /// Retract the vote a receipt was handed out for
+    Retract {
+        /// Receipt printed when the vote was cast
+        receipt: String,
+    },
+    /// List all votes
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum VoterCommand {
+    /// Set how much each vote of a voter counts
+    Weight {
+        /// Name of the voter
+        name: String,
+        /// Voting power of the votes cast from now on
+        weight: i64,
+    },
+    /// List all voters
+    List,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum UserCommand {
+    /// Create an account. The password is asked for, or read from RUST_TRABALHO_PASSWORD. The first account
+    /// is an admin, the others are voters until an admin gives them a role
+    Create {
+        name: String,
+    },
+    /// Print the code a voter without an account signs up with, keeping their weight and votes
+    Invite {
+        name: String,
+    },
+    /// Create the account of the voter an invite was given to. The password is asked for like on create
+    Join {
+        code: String,
+    },
+    /// Give a user a role: admin, poll_manager, voter or observer
+    Role {
+        name: String,
+        role: String,
+    },
+    /// List all users
+    List,
+}
+
+// Scripts can give the password in RUST_TRABALHO_PASSWORD, otherwise it is typed without being shown
+fn read_password(prompt: &str) -> Result<String, Box<dyn Error>> {
+    match std::env::var("RUST_TRABALHO_PASSWORD") {
+        Ok(password) => Ok(password),
+        Err(_) => Ok(rpassword::prompt_password(prompt)?),
+    }
+}
+
+// A password for a new account, typed twice unless it comes from RUST_TRABALHO_PASSWORD
+fn read_new_password() -> Result<String, Box<dyn Error>> {
+    let password = read_password("Password: ")?;
+
+    if std::env::var("RUST_TRABALHO_PASSWORD").is_err() && rpassword::prompt_password("Repeat the password: ")? != password {
+        return Err("The passwords don't match.".into());
+    }
+
+    Ok(password)
+}
+
+pub fn login(conn: &Connection, name: &str) -> Result<User, Box<dyn Error>> {
+    let password = read_password(&format!("Password for {}: ", name))?;
+
+    Ok(user::login(conn, name, &password)?)
+}
+
+fn logged_in(user: Option<&User>) -> Result<&User, PollError> {
+    user.ok_or(PollError::LoginRequired)
+}
+
+// Reading is open to anyone, changing anything needs a login
+fn needs_login(command: &Command) -> bool {
+    match command {
+        Command::Poll { command } => !matches!(command, PollCommand::List | PollCommand::History { .. }),
+        Command::Vote { command } => !matches!(command, VoteCommand::Check { .. } | VoteCommand::List),
+        Command::Voter { command } => !matches!(command, VoterCommand::List),
+        Command::Verify { repair } => *repair,
+        Command::User { command } => matches!(command, UserCommand::Role { .. } | UserCommand::Invite { .. }),
+        Command::Results { .. } | Command::Audit { .. } | Command::VerifyLedger { .. } | Command::Serve { .. } => false,
+    }
+}
+
+fn change_status(conn: &Connection, user: Option<&User>, input: &str, status: PollStatus, reason: String) -> Result<(), Box<dyn Error>> {
+    let poll = find_poll(conn, input)?;
+
+    permission::check_poll(logged_in(user)?, &poll)?;
+
+    let poll = poll::set_poll_status(conn, &poll.id, status, reason)?;
+
+    println!("{}  {}", poll.id, poll_status(&poll));
+
+    Ok(())
+}
+
+fn find_poll(conn: &Connection, input: &str) -> Result<Poll, Box<dyn Error>> {
+    match poll::find_poll(conn, input)? {
+        Some(poll) => Ok(poll),
+        None => Err(PollError::PollNotFound(input.to_string()).into()),
+    }
+}
+
+fn find_receipt(conn: &Connection, receipt: &str) -> Result<Vote, Box<dyn Error>> {
+    match vote::get_vote_by_receipt(conn, receipt)? {
+        Some(vote) => Ok(vote),
+        None => Err(PollError::ReceiptNotFound.into()),
+    }
+}
+
+fn poll_status(poll: &Poll) -> &'static str {
+    if poll.status == PollStatus::Draft {
+        "Draft"
+    } else if poll.status == PollStatus::Archived {
+        "Archived"
+    } else if poll.is_closed() {
+        "Closed"
+    } else if !poll.has_started() {
+        "Scheduled"
+    } else {
+        "Open"
+    }
+}
+
+// A poll flattened to one row per option, since csv can't hold the options list
+#[derive(Serialize)]
+struct ResultRow<'a> {
+    poll_id: Uuid,
+    question: &'a str,
+    status: &'a str,
+    status_reason: &'a str,
+    kind: String,
+    option_id: Uuid,
+    label: &'a str,
+    votes: i64,
+    average_score: Option<f64>,
+    abstentions: i64,
+    quorum: i64,
+    threshold: String,
+    anonymous: bool,
+    outcome: Outcome,
+    winner: Option<&'a str>,
+    poll_duration: String,
+    create_date: String,
+    start_date: String,
+    expiration_date: String,
+}
+
+fn rfc3339(date: i64) -> String {
+    Local.timestamp_opt(date, 0).unwrap().to_rfc3339()
+}
+
+pub fn polls_to_csv(polls: &[Poll]) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    for poll in polls {
+        let results = poll.results();
+
+        for option in &poll.options {
+            writer.serialize(ResultRow {
+                poll_id: poll.id,
+                question: &poll.question,
+                status: poll_status(poll),
+                status_reason: &poll.status_reason,
+                kind: poll.kind.to_string(),
+                option_id: option.id,
+                label: &option.label,
+                votes: option.votes,
+                average_score: poll.average_score(option),
+                abstentions: poll.abstentions,
+                quorum: poll.rules.quorum,
+                threshold: poll.rules.threshold.to_string(),
+                anonymous: poll.rules.anonymous,
+                outcome: results.outcome,
+                winner: results.winner,
+                poll_duration: poll.poll_duration.to_string(),
+                create_date: rfc3339(poll.create_date),
+                start_date: rfc3339(poll.start_date),
+                expiration_date: rfc3339(poll.expiration_date),
+            })?;
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+pub fn votes_to_csv(votes: &[Vote]) -> Result<String, Box<dyn Error>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+
+    for vote in votes {
+        writer.serialize(vote)?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+fn print_polls(polls: &[Poll], format: Format) -> Result<(), Box<dyn Error>> {
+    match format {
+        Format::Text => {
+            for poll in polls {
+                print_poll_results(poll);
+            }
+        }
+        Format::Json => {
+            let results: Vec<PollResults> = polls.iter().map(Poll::results).collect();
+
+            println!("{}", serde_json::to_string_pretty(&results)?)
+        }
+        Format::Csv => print!("{}", polls_to_csv(polls)?),
+    }
+
+    Ok(())
+}
+
+// One line per event, with what the vote was before and after it
+pub fn print_vote_events(events: &[VoteEvent]) {
+    if events.is_empty() {
+        println!("No votes were cast yet.");
+    }
+
+    for event in events {
+        let change = match (&event.previous_label, &event.option_label) {
+            (Some(previous), Some(label)) => format!("{} -> {}", previous, label),
+            (None, Some(label)) => label.clone(),
+            (Some(previous), None) => previous.clone(),
+            (None, None) => String::new(),
+        };
+
+        print!("{}  {}  {}  {}", Local.timestamp_opt(event.event_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"), event.voter_name, event.action, change);
+
+        match (&event.previous_comment, &event.comment) {
+            (Some(previous), Some(comment)) if previous != comment => println!("  (comment \"{}\" -> \"{}\")", previous, comment),
+            (None, Some(comment)) if !comment.is_empty() => println!("  (comment \"{}\")", comment),
+            _ => println!(),
+        }
+    }
+}
+
+// The poll as it is now, then what it was before each edit, oldest first
+pub fn print_poll_history(poll: &Poll, revisions: &[PollRevision]) {
+    println!("\nQuestion: {}", poll.question);
+    println!("Create Date: {}", Local.timestamp_opt(poll.create_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"));
+
+    if revisions.is_empty() {
+        println!("This poll was never edited.");
+    }
+
+    for revision in revisions {
+        println!("\nEdited by {} on {}", revision.editor_name, Local.timestamp_opt(revision.edit_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"));
+        println!("Old Question: {}", revision.question);
+        println!("Old Poll Duration: {} (expiring {})", revision.poll_duration, Local.timestamp_opt(revision.expiration_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"));
+    }
+}
+
+// Ranked polls show their first preferences above and each instant runoff round here
+fn print_runoff(poll: &Poll) {
+    for (i, round) in poll.runoff.iter().enumerate() {
+        let tallies: Vec<String> = round.tallies.iter().map(|tally| format!("{} {}", tally.label, tally.votes)).collect();
+
+        print!("Round {}: {}", i + 1, tallies.join(", "));
+
+        if round.exhausted > 0 {
+            print!(", exhausted {}", round.exhausted);
+        }
+
+        let eliminated: Vec<&str> = round.tallies
+            .iter()
+            .filter(|tally| round.eliminated.contains(&tally.option_id))
+            .map(|tally| tally.label.as_str())
+            .collect();
+
+        if eliminated.is_empty() {
+            println!();
+        } else {
+            println!(" ({} eliminated)", eliminated.join(", "));
+        }
+    }
+}
+
+pub fn print_poll_results(poll: &Poll) {
+    let create_date = Local.timestamp_opt(poll.create_date, 0).unwrap();
+    let start_date = Local.timestamp_opt(poll.start_date, 0).unwrap();
+    let expiration_date = Local.timestamp_opt(poll.expiration_date, 0).unwrap();
+
+    println!("\nQuestion: {}", poll.question);
+    if poll.status_reason.is_empty() {
+        println!("Status: {}", poll_status(poll));
+    } else {
+        println!("Status: {} ({})", poll_status(poll), poll.status_reason);
+    }
+
+    for option in &poll.options {
+        match poll.kind {
+            PollKind::Single | PollKind::Ranked => println!("{}: {} Votes", option.label, option.votes),
+            PollKind::Approval => println!("{}: {} Approvals", option.label, option.votes),
+            PollKind::Score => println!("{}: {:.2} Average ({} Points)", option.label, poll.average_score(option).unwrap_or_default(), option.votes),
+        }
+    }
+
+    if poll.kind == PollKind::Ranked {
+        print_runoff(poll);
+    }
+
+    println!("{}: {} Votes", vote::ABSTAIN_LABEL, poll.abstentions);
+    println!("Turnout: {} Votes", poll.turnout());
+
+    if poll.rules.quorum > 0 {
+        println!("Quorum: {} Votes", poll.rules.quorum);
+    }
+
+    println!("Threshold: {}", poll.rules.threshold);
+
+    if poll.rules.anonymous {
+        println!("Votes: Secret");
+    }
+
+    match poll.results() {
+        PollResults { outcome, winner: Some(winner), .. } => println!("Outcome: {} ({})", outcome, winner),
+        PollResults { outcome, .. } => println!("Outcome: {}", outcome),
+    }
+
+    println!("Create Date: {}\nStart Date: {}\nExpiration Date: {} \nPoll Duration: {}",
+    create_date.format("%d-%m-%Y %H:%M:%S"),
+    start_date.format("%d-%m-%Y %H:%M:%S"),
+    expiration_date.format("%d-%m-%Y %H:%M:%S"),
+    poll.poll_duration);
+}
+
+pub fn run(conn: &Connection, command: Command, format: Format, user: Option<&User>) -> Result<(), Box<dyn Error>> {
+    let user = match user {
+        None if needs_login(&command) => return Err(PollError::LoginRequired.into()),
+        user => user,
+    };
+
+    match command {
+        Command::Poll { command: PollCommand::Create { question, days, duration, options, start, quorum, threshold, kind, anonymous } } => {
+            let input_duration = match (days, duration) {
+                (Some(days), _) => format!("{}d", days),
+                (None, Some(duration)) => duration,
+                (None, None) => unreachable!("clap requires --days or --duration"),
+            };
+
+            let options = if options.is_empty() {
+                poll::DEFAULT_OPTIONS.iter().map(|option| option.to_string()).collect()
+            } else {
+                options
+            };
+
+            permission::check(logged_in(user)?, Action::CreatePoll)?;
+
+            let rules = poll::PollRules { quorum, threshold: threshold.parse()?, anonymous };
+
+            let poll = poll::create_poll(conn, question, input_duration, options, start, rules, kind.parse()?, user)?;
+
+            println!("{}", poll.id);
+        }
+        Command::Poll { command: PollCommand::List } => {
+            let polls = poll::get_polls(conn)?;
+
+            match format {
+                Format::Text => {
+                    for poll in polls {
+                        println!("{}  {:<9}  {}", poll.id, poll_status(&poll), poll.question);
+                    }
+                }
+                _ => print_polls(&polls, format)?,
+            }
+        }
+        Command::Poll { command: PollCommand::History { poll } } => {
+            let poll = find_poll(conn, &poll)?;
+            let revisions = poll::get_poll_revisions(conn, &poll.id)?;
+
+            match format {
+                Format::Text => print_poll_history(&poll, &revisions),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&revisions)?),
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+
+                    for revision in &revisions {
+                        writer.serialize(revision)?;
+                    }
+
+                    print!("{}", String::from_utf8(writer.into_inner()?)?);
+                }
+            }
+        }
+        Command::Poll { command: PollCommand::Close { poll, reason } } => change_status(conn, user, &poll, PollStatus::Closed, reason)?,
+        Command::Poll { command: PollCommand::Reopen { poll, reason } } => change_status(conn, user, &poll, PollStatus::Open, reason)?,
+        Command::Poll { command: PollCommand::Draft { poll, reason } } => change_status(conn, user, &poll, PollStatus::Draft, reason)?,
+        Command::Poll { command: PollCommand::Archive { poll, reason } } => change_status(conn, user, &poll, PollStatus::Archived, reason)?,
+        Command::Vote { command: VoteCommand::Cast { poll, choice, comment } } => {
+            let user = logged_in(user)?;
+
+            permission::check(user, Action::CastVote)?;
+
+            let poll = find_poll(conn, &poll)?;
+            let voter = user::user_voter(conn, user)?;
+
+            let vote_choice = match poll.find_choice(&choice) {
+                Some(vote_choice) => vote_choice,
+                None => return Err(PollError::InvalidChoice(choice).into()),
+            };
+
+            let vote = vote::create_vote(conn, poll, &voter, vote_choice, comment)?;
+
+            println!("{}", vote.id);
+            println!("Receipt: {} (keep it to check, change or retract this vote)", vote.receipt.unwrap_or_default());
+        }
+        Command::Vote { command: VoteCommand::Check { receipt } } => {
+            let vote = find_receipt(conn, &receipt)?;
+
+            match format {
+                Format::Text => {
+                    let create_date = Local.timestamp_opt(vote.create_date, 0).unwrap();
+
+                    println!("{}  {}  {}  {}  {}", vote.id, create_date.format("%d-%m-%Y %H:%M:%S"), vote.voter_name, vote.option_label, vote.poll_question);
+                }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&vote)?),
+                Format::Csv => print!("{}", votes_to_csv(&[vote])?),
+            }
+        }
+        Command::Vote { command: VoteCommand::Change { receipt, choice, comment } } => {
+            let current_vote = find_receipt(conn, &receipt)?;
+
+            permission::check_vote(logged_in(user)?, &current_vote)?;
+
+            let poll = find_poll(conn, &current_vote.poll_id.to_string())?;
+
+            let vote_choice = match poll.find_choice(&choice) {
+                Some(vote_choice) => vote_choice,
+                None => return Err(PollError::InvalidChoice(choice).into()),
+            };
+
+            let vote = vote::edit_vote_by_receipt(conn, &receipt, vote_choice, comment)?;
+
+            println!("{}  {}", vote.id, vote.option_label);
+        }
+        Command::Vote { command: VoteCommand::Retract { receipt } } => {
+            permission::check_vote(logged_in(user)?, &find_receipt(conn, &receipt)?)?;
+
+            let vote = vote::delete_vote_by_receipt(conn, &receipt)?;
+
+            println!("{}  retracted", vote.id);
+        }
+        Command::Vote { command: VoteCommand::List } => {
+            let votes = vote::get_votes(conn)?;
+
+            match format {
+                Format::Text => {
+                    for vote in votes {
+                        let create_date = Local.timestamp_opt(vote.create_date, 0).unwrap();
+
+                        println!("{}  {}  {}  {}  {}", vote.id, create_date.format("%d-%m-%Y %H:%M:%S"), vote.voter_name, vote.option_label, vote.poll_question);
+                    }
+                }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&votes)?),
+                Format::Csv => print!("{}", votes_to_csv(&votes)?),
+            }
+        }
+        Command::Voter { command: VoterCommand::Weight { name, weight } } => {
+            permission::check(logged_in(user)?, Action::ManageVoters)?;
+
+            let voter = voter::set_voter_weight(conn, name, weight)?;
+
+            println!("{} now votes with weight {}", voter.name, voter.weight);
+        }
+        Command::Voter { command: VoterCommand::List } => {
+            let voters = voter::get_voters(conn)?;
+
+            match format {
+                Format::Text => {
+                    for voter in voters {
+                        println!("{}  {:>6}  {}", voter.id, voter.weight, voter.name);
+                    }
+                }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&voters)?),
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+
+                    for voter in &voters {
+                        writer.serialize(voter)?;
+                    }
+
+                    print!("{}", String::from_utf8(writer.into_inner()?)?);
+                }
+            }
+        }
+        Command::User { command: UserCommand::Create { name } } => {
+            let password = read_new_password()?;
+            let user = user::create_user(conn, name, &password)?;
+
+            println!("{}  {}  {}", user.id, user.name, user.role);
+        }
+        Command::User { command: UserCommand::Invite { name } } => {
+            permission::check(logged_in(user)?, Action::ManageUsers)?;
+
+            let code = user::invite_voter(conn, &name)?;
+
+            println!("{} can sign up with: user join {}", name.trim(), code);
+        }
+        Command::User { command: UserCommand::Join { code } } => {
+            let password = read_new_password()?;
+            let user = user::join(conn, &code, &password)?;
+
+            println!("{}  {}  {}", user.id, user.name, user.role);
+        }
+        Command::User { command: UserCommand::Role { name, role } } => {
+            permission::check(logged_in(user)?, Action::ManageUsers)?;
+
+            let role: Role = role.parse()?;
+            let user = user::set_user_role(conn, &name, role)?;
+
+            println!("{} is now {}", user.name, user.role);
+        }
+        Command::User { command: UserCommand::List } => {
+            let users = user::get_users(conn)?;
+
+            match format {
+                Format::Text => {
+                    for user in users {
+                        println!("{}  {}  {}", user.id, user.name, user.role);
+                    }
+                }
+                Format::Json => println!("{}", serde_json::to_string_pretty(&users)?),
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+
+                    for user in &users {
+                        writer.serialize(user)?;
+                    }
+
+                    print!("{}", String::from_utf8(writer.into_inner()?)?);
+                }
+            }
+        }
+        Command::Results { poll: Some(poll) } => {
+            let poll = find_poll(conn, &poll)?;
+
+            match format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&poll.results())?),
+                _ => print_polls(&[poll], format)?,
+            }
+        }
+        Command::Results { poll: None } => {
+            print_polls(&poll::get_polls(conn)?, format)?;
+        }
+        Command::Audit { poll, voter } => {
+            let events = match (poll, voter) {
+                (Some(poll), _) => {
+                    // The log outlives the poll, so a full id is used as it is
+                    let poll_id = match Uuid::parse_str(&poll) {
+                        Ok(poll_id) => poll_id,
+                        Err(_) => find_poll(conn, &poll)?.id,
+                    };
+
+                    vote::get_poll_events(conn, &poll_id)?
+                }
+                (None, Some(name)) => match voter::find_voter(conn, &name)? {
+                    Some(voter) => vote::get_voter_events(conn, &voter.id)?,
+                    None => return Err(format!("Voter not found: {}", name).into()),
+                },
+                (None, None) => Vec::new(),
+            };
+
+            match format {
+                Format::Text => print_vote_events(&events),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&events)?),
+                Format::Csv => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+
+                    for event in &events {
+                        writer.serialize(event)?;
+                    }
+
+                    print!("{}", String::from_utf8(writer.into_inner()?)?);
+                }
+            }
+        }
+        Command::Verify { repair } => {
+            if repair {
+                permission::check(logged_in(user)?, Action::ManageVoters)?;
+            }
+
+            let mismatches = poll::verify_tallies(conn, repair)?;
+
+            for mismatch in &mismatches {
+                println!("{}  {}: stored {}, counted {}", mismatch.poll_id, mismatch.label, mismatch.stored_votes, mismatch.counted_votes);
+            }
+
+            if mismatches.is_empty() {
+                println!("All vote counters match the votes.");
+            } else if repair {
+                println!("{} vote counters fixed.", mismatches.len());
+            } else {
+                return Err(format!("{} vote counters are wrong. Run with --repair to fix them.", mismatches.len()).into());
+            }
+        }
+        Command::VerifyLedger { head } => {
+            let report = ledger::verify_ledger(conn)?;
+
+            if let Some(problem) = report.problem {
+                return Err(problem.to_string().into());
+            }
+
+            if let Some(head) = head {
+                if !ledger::has_head(conn, &head)? {
+                    return Err(format!("The vote log doesn't have the entry {} any more, so it was rewritten since that head was printed.", head.trim()).into());
+                }
+            }
+
+            println!("The vote log is intact: {} entries, all matching the votes.", report.entries);
+            println!("Head: {}", report.head);
+        }
+        Command::Serve { addr } => {
+            println!("Listening on http://{}", addr);
+
+            server::serve(conn, &addr)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,143 @@
+use clap::Parser;
+use rusqlite::{Connection, Result};
+
+use rust_trabalho::permission::Action;
+use rust_trabalho::{create_tables, poll, user, vote, voter, PollError, PollKind, PollRules, Role, VoteChoice};
+
+use super::{polls_to_csv, run, votes_to_csv, Cli, Command, Format, PollCommand};
+
+fn yes_no() -> Vec<String> {
+    vec!["Yes".to_string(), "No".to_string()]
+}
+
+fn parse(args: &[&str]) -> Command {
+    Cli::try_parse_from(args).expect("Valid arguments").command.expect("A subcommand")
+}
+
+#[test]
+fn test_no_arguments_opens_menu() {
+    let cli = Cli::try_parse_from(["rust-trabalho"]).expect("Valid arguments");
+
+    assert!(cli.command.is_none());
+    assert_eq!(cli.database, "database.db");
+}
+
+#[test]
+fn test_poll_create_needs_a_duration() {
+    assert!(Cli::try_parse_from(["rust-trabalho", "poll", "create", "--question", "Lunch?"]).is_err());
+    assert!(Cli::try_parse_from(["rust-trabalho", "poll", "create", "--question", "Lunch?", "--days", "7", "--duration", "36h"]).is_err());
+}
+
+#[test]
+fn test_create_poll_and_cast_vote() -> Result<()> {
+    let conn = Connection::open_in_memory()?;
+
+    create_tables(&conn)?;
+
+    let ana = user::create_user(&conn, "Ana".to_string(), "correct horse").expect("User to be created");
+    let bruno = user::create_user(&conn, "Bruno".to_string(), "battery staple").expect("User to be created");
+
+    let command = parse(&["rust-trabalho", "poll", "create", "--question", "Lunch?", "--days", "7", "--option", "Pizza", "--option", "Sushi"]);
+    assert!(matches!(command, Command::Poll { command: PollCommand::Create { .. } }));
+
+    // Changing anything needs a login, reading doesn't
+    let error = run(&conn, parse(&["rust-trabalho", "poll", "create", "--question", "Lunch?", "--days", "7"]), Format::Text, None).unwrap_err();
+    assert_eq!(error.downcast_ref::<PollError>(), Some(&PollError::LoginRequired));
+
+    run(&conn, command, Format::Text, Some(&ana)).expect("Poll to be created");
+    run(&conn, parse(&["rust-trabalho", "poll", "list"]), Format::Text, None).expect("Polls to be listed");
+
+    let polls = poll::get_polls(&conn)?;
+    assert_eq!(polls.len(), 1);
+    assert_eq!(polls[0].poll_duration, poll::PollDuration::Days(7));
+    assert_eq!(polls[0].creator_id, Some(ana.id));
+
+    let poll_id = polls[0].id.to_string();
+
+    run(&conn, parse(&["rust-trabalho", "vote", "cast", "--poll", &poll_id, "--choice", "sushi"]), Format::Text, Some(&ana)).expect("Vote to be cast");
+
+    let votes = vote::get_votes(&conn)?;
+    assert_eq!(votes.len(), 1);
+    assert_eq!(votes[0].option_label, "Sushi");
+    assert_eq!(votes[0].voter_id, Some(ana.voter_id));
+
+    let error = run(&conn, parse(&["rust-trabalho", "vote", "cast", "--poll", &poll_id, "--choice", "Burger"]), Format::Text, Some(&bruno)).unwrap_err();
+    assert_eq!(error.downcast_ref::<PollError>(), Some(&PollError::InvalidChoice("Burger".to_string())));
+
+    let error = run(&conn, parse(&["rust-trabalho", "results", "not-a-poll"]), Format::Text, None).unwrap_err();
+    assert_eq!(error.downcast_ref::<PollError>(), Some(&PollError::PollNotFound("not-a-poll".to_string())));
+
+    Ok(())
+}
+
+#[test]
+fn test_user_roles() -> Result<()> {
+    let conn = Connection::open_in_memory()?;
+
+    create_tables(&conn)?;
+
+    let ana = user::create_user(&conn, "Ana".to_string(), "correct horse").expect("User to be created");
+    let bruno = user::create_user(&conn, "Bruno".to_string(), "battery staple").expect("User to be created");
+
+    // Voters can't create polls or hand out roles until an admin makes them poll managers
+    let error = run(&conn, parse(&["rust-trabalho", "poll", "create", "--question", "Lunch?", "--days", "7"]), Format::Text, Some(&bruno)).unwrap_err();
+    assert_eq!(error.downcast_ref::<PollError>(), Some(&PollError::PermissionDenied(Action::CreatePoll)));
+
+    let error = run(&conn, parse(&["rust-trabalho", "user", "role", "Bruno", "admin"]), Format::Text, Some(&bruno)).unwrap_err();
+    assert_eq!(error.downcast_ref::<PollError>(), Some(&PollError::PermissionDenied(Action::ManageUsers)));
+
+    run(&conn, parse(&["rust-trabalho", "user", "role", "Bruno", "poll-manager"]), Format::Text, Some(&ana)).expect("Role to be set");
+
+    let bruno = user::find_user(&conn, "Bruno")?.expect("User to exist");
+    assert_eq!(bruno.role, Role::PollManager);
+
+    run(&conn, parse(&["rust-trabalho", "poll", "create", "--question", "Lunch?", "--days", "7"]), Format::Text, Some(&bruno)).expect("Poll to be created");
+
+    Ok(())
+}
+
+#[test]
+fn test_poll_json() -> Result<()> {
+    let conn = Connection::open_in_memory()?;
+
+    create_tables(&conn)?;
+
+    let poll = poll::create_poll(&conn, "Lunch?".to_string(), "36h".to_string(), yes_no(), "2099-01-01 12:00".to_string(), PollRules::default(), PollKind::Single, None).expect("Poll to be created");
+    let json = serde_json::to_value(&poll).expect("Poll to be serialized");
+
+    assert_eq!(json["id"], poll.id.to_string());
+    assert_eq!(json["poll_duration"], "36h");
+    assert_eq!(json["options"][0]["label"], "Yes");
+    assert_eq!(json["options"][0]["votes"], 0);
+
+    let start_date = chrono::DateTime::parse_from_rfc3339(json["start_date"].as_str().unwrap()).expect("RFC 3339 start date");
+    assert_eq!(start_date.timestamp(), poll.start_date);
+
+    Ok(())
+}
+
+#[test]
+fn test_results_and_votes_csv() -> Result<()> {
+    let conn = Connection::open_in_memory()?;
+
+    create_tables(&conn)?;
+
+    let created = poll::create_poll(&conn, "Lunch, or not?".to_string(), "3d".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None).expect("Poll to be created");
+    let voter = voter::create_voter(&conn, "Ana".to_string()).expect("Voter to be created");
+    vote::create_vote(&conn, created.clone(), &voter, VoteChoice::Option(created.options[1].id), "".to_string()).expect("Vote to be created");
+
+    let polls = poll::get_polls(&conn)?;
+    let csv = polls_to_csv(&polls).expect("Polls to be written");
+    let lines: Vec<&str> = csv.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "poll_id,question,status,status_reason,kind,option_id,label,votes,average_score,abstentions,quorum,threshold,anonymous,outcome,winner,poll_duration,create_date,start_date,expiration_date");
+    assert!(lines[2].starts_with(&format!("{},\"Lunch, or not?\",Open,,single,{},No,1,,0,0,majority,false,pending,,3d,", created.id, created.options[1].id)));
+
+    let csv = votes_to_csv(&vote::get_votes(&conn)?).expect("Votes to be written");
+
+    assert_eq!(csv.lines().count(), 2);
+    assert!(csv.lines().nth(1).unwrap().contains(",No,,1,"));
+
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use uuid::Uuid;
+use crate::permission::Action;
+use crate::poll::{PollKind, PollStatus};
+use chrono::{Local, TimeZone};
+use std::fmt;
+use std::error::Error;
+
+// Every way a poll, vote, voter or user operation can fail. The messages are the ones shown to the user
+#[derive(Debug, PartialEq)]
+pub enum PollError {
+    QuestionEmpty,
+    QuestionTooLong { max: usize },
+    InvalidDuration,
+    InvalidStartDate,
+    StartDateInPast,
+    EndsBeforeStart,
+    EndsInPast,
+    TooFewOptions { min: usize },
+    TooManyOptions { max: usize },
+    OptionEmpty,
+    OptionTooLong { max: usize },
+    DuplicateOptions,
+    InvalidQuorum,
+    InvalidThreshold,
+    InvalidPollKind,
+    InvalidPollStatus,
+    InvalidStatusChange { from: PollStatus, to: PollStatus },
+    PollHasVotes,
+    ReasonTooLong { max: usize },
+    InvalidConfirmation,
+    NotConfirmed,
+    PollNotFound(String), // The id or short code looked for
+    AmbiguousShortCode(String),
+    PollClosed { poll_id: Uuid, expiration_date: i64 },
+    PollNotOpen { poll_id: Uuid, status: PollStatus },
+    PollNotStarted { poll_id: Uuid, start_date: i64 },
+    AlreadyVoted { poll_id: Uuid, voter_id: Uuid },
+    OptionNotInPoll { poll_id: Uuid, option_id: Uuid },
+    InvalidChoice(String), // What was typed, matching none of the options
+    VoteNotFound(Uuid),
+    AnonymousVote(Uuid),
+    ReceiptNotFound,
+    CommentTooLong { max: usize },
+    BallotNotAllowed(PollKind),
+    InvalidRanking,
+    InvalidApproval,
+    InvalidScores { max: i64 },
+    VoterNameEmpty,
+    VoterNameTooLong { max: usize },
+    VoterNameTaken,
+    VoterNotFound,
+    VoterHasUser,
+    InvalidInvite,
+    InvalidWeight,
+    UserNameEmpty,
+    UserNameTooLong { max: usize },
+    UserNameTaken,
+    PasswordTooShort { min: usize },
+    InvalidLogin,
+    LoginRequired,
+    UserNotFound,
+    InvalidRole,
+    LastAdmin,
+    NoAdmin,
+    PermissionDenied(Action),
+    Database(rusqlite::Error),
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PollError::QuestionEmpty => write!(f, "Question can't be empty."),
+            PollError::QuestionTooLong { max } => write!(f, "Question is too long. Question only can have up to {} chars.", max),
+            PollError::InvalidDuration => write!(f, "Invalid input for Poll Duration. Use hours (36h), days (3d) or an end date (2024-12-31)."),
+            PollError::InvalidStartDate => write!(f, "Invalid input for Start Date. Use a date like 2024-12-31 or 2024-12-31 18:00."),
+            PollError::StartDateInPast => write!(f, "Start date can't be in the past."),
+            PollError::EndsBeforeStart => write!(f, "The poll must end after it starts."),
+            PollError::EndsInPast => write!(f, "The poll must end in the future."),
+            PollError::TooFewOptions { min } => write!(f, "A poll needs at least {} options.", min),
+            PollError::TooManyOptions { max } => write!(f, "A poll can have up to {} options.", max),
+            PollError::OptionEmpty => write!(f, "Options can't be empty."),
+            PollError::OptionTooLong { max } => write!(f, "Option is too long. Options only can have up to {} chars.", max),
+            PollError::DuplicateOptions => write!(f, "Options must be unique."),
+            PollError::InvalidQuorum => write!(f, "Quorum can't be negative."),
+            PollError::InvalidThreshold => write!(f, "Invalid input for Threshold. Use majority, a fraction (2/3) or a percentage (60%)."),
+            PollError::InvalidPollKind => write!(f, "Invalid input for Poll Kind. Use single, ranked, approval or score."),
+            PollError::InvalidPollStatus => write!(f, "Invalid input for Poll Status. Use draft, open, closed or archived."),
+            PollError::InvalidStatusChange { from, to } => write!(f, "This poll is {}, so it can't be {}.", from, match to {
+                PollStatus::Draft => "turned into a draft",
+                PollStatus::Open => "opened",
+                PollStatus::Closed => "closed",
+                PollStatus::Archived => "archived",
+            }),
+            PollError::PollHasVotes => write!(f, "This poll already has votes, so it can't go back to being a draft."),
+            PollError::ReasonTooLong { max } => write!(f, "Reason is too long. Reason only can have up to {} chars.", max),
+            PollError::InvalidConfirmation => write!(f, "Invalid input for choice 2. Please enter 'y' or 'n'."),
+            PollError::NotConfirmed => write!(f, "Canceling operation. Not a Valid Confirmation."),
+            PollError::PollNotFound(_) => write!(f, "Poll not found."),
+            PollError::AmbiguousShortCode(_) => write!(f, "More than one poll starts with this code. Use more characters or the full id."),
+            PollError::PollClosed { .. } => write!(f, "This poll is closed. Votes can't be changed after the expiration date."),
+            PollError::PollNotOpen { status: PollStatus::Draft, .. } => write!(f, "This poll is still a draft. It takes votes once it is opened."),
+            PollError::PollNotOpen { status, .. } => write!(f, "This poll was {}. Votes can't be changed unless it is reopened.", status),
+            PollError::PollNotStarted { start_date, .. } => write!(f, "This poll didn't start yet. Voting opens at {}.", Local.timestamp_opt(*start_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S")),
+            PollError::AlreadyVoted { .. } => write!(f, "You have already voted on this poll."),
+            PollError::OptionNotInPoll { .. } => write!(f, "Invalid Vote. The option doesn't belong to this poll."),
+            PollError::InvalidChoice(choice) => write!(f, "Invalid Vote. \"{}\" doesn't match the options of this poll.", choice.trim()),
+            PollError::VoteNotFound(_) => write!(f, "Vote not found."),
+            PollError::AnonymousVote(_) => write!(f, "This vote was cast on an anonymous poll. It can't be changed or retracted, since it isn't tied to a voter. Use the receipt you got when voting."),
+            PollError::ReceiptNotFound => write!(f, "No vote has this receipt. Check the code and try again."),
+            PollError::CommentTooLong { max } => write!(f, "Comment is too long. Comment only can have up to {} chars.", max),
+            PollError::BallotNotAllowed(kind) => write!(f, "Invalid Vote. This poll takes {}.", kind.ballot_description()),
+            PollError::InvalidRanking => write!(f, "Invalid Vote. Rank each option at most once."),
+            PollError::InvalidApproval => write!(f, "Invalid Vote. Approve at least one option, each at most once."),
+            PollError::InvalidScores { max } => write!(f, "Invalid Vote. Give every option one score from 0 to {}.", max),
+            PollError::VoterNameEmpty => write!(f, "Voter name can't be empty."),
+            PollError::VoterNameTooLong { max } => write!(f, "Voter name is too long. Voter name only can have up to {} chars.", max),
+            PollError::VoterNameTaken => write!(f, "There is already a voter with this name."),
+            PollError::VoterNotFound => write!(f, "Voter not found."),
+            PollError::VoterHasUser => write!(f, "This voter already has an account."),
+            PollError::InvalidInvite => write!(f, "This invite isn't valid. Ask for a new one."),
+            PollError::InvalidWeight => write!(f, "Voter weight must be a whole number of at least 1."),
+            PollError::UserNameEmpty => write!(f, "User name can't be empty."),
+            PollError::UserNameTooLong { max } => write!(f, "User name is too long. User name only can have up to {} chars.", max),
+            PollError::UserNameTaken => write!(f, "There is already a user or voter with this name."),
+            PollError::PasswordTooShort { min } => write!(f, "Password is too short. It needs at least {} chars.", min),
+            PollError::InvalidLogin => write!(f, "Wrong user name or password."),
+            PollError::LoginRequired => write!(f, "You need to log in to do this."),
+            PollError::UserNotFound => write!(f, "User not found."),
+            PollError::InvalidRole => write!(f, "Invalid role. Use admin, poll_manager, voter or observer."),
+            PollError::LastAdmin => write!(f, "This is the last admin. Make someone else an admin first."),
+            PollError::NoAdmin => write!(f, "There is no admin yet. Create the first account from the command line with user create."),
+            PollError::PermissionDenied(action) => write!(f, "Permission denied. {}", action.who_can()),
+            PollError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+impl Error for PollError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PollError::Database(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for PollError {
+    fn from(err: rusqlite::Error) -> PollError {
+        PollError::Database(err)
+    }
+}
@@ -0,0 +1,185 @@
+use rusqlite::{Connection, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use uuid::Uuid;
+
+use crate::vote::{self, VoteAction, VoteEvent};
+
+// What the first entry of the log points at, since there is nothing before it
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+// Why verify_ledger stopped
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LedgerIssue {
+    BrokenChain, // The entry doesn't point at the one before it, so entries were removed or reordered
+    HashMismatch, // The entry was changed after it was written
+    VoteMissing, // The log has a vote the Vote table lost
+    VoteDiffers, // The Vote row doesn't match the last entry about it
+    VoteNotLogged, // The Vote table has a vote the log never saw
+}
+
+impl fmt::Display for LedgerIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerIssue::BrokenChain => write!(f, "doesn't follow the entry before it"),
+            LedgerIssue::HashMismatch => write!(f, "was changed after it was written"),
+            LedgerIssue::VoteMissing => write!(f, "is for a vote that is missing from the Vote table"),
+            LedgerIssue::VoteDiffers => write!(f, "doesn't match the vote in the Vote table"),
+            LedgerIssue::VoteNotLogged => write!(f, "is in the Vote table but not in the log"),
+        }
+    }
+}
+
+// The first inconsistency found. Votes that were never logged have no entry to point at
+#[derive(Debug, PartialEq, Clone)]
+pub struct LedgerProblem {
+    pub entry: Option<usize>, // Position in the log, starting at 1
+    pub event_id: Option<Uuid>,
+    pub vote_id: Uuid,
+    pub issue: LedgerIssue,
+}
+
+impl fmt::Display for LedgerProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.entry, self.event_id) {
+            (Some(entry), Some(event_id)) => write!(f, "Entry #{} ({}) for vote {} {}.", entry, event_id, self.vote_id, self.issue),
+            _ => write!(f, "Vote {} {}.", self.vote_id, self.issue),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct LedgerReport {
+    pub entries: usize,
+    pub head: String, // Hash of the last entry, to keep outside the database and check with has_head later
+    pub problem: Option<LedgerProblem>,
+}
+
+// The hash the next entry has to point at
+pub fn last_hash(conn: &Connection) -> Result<String> {
+    let hash: Option<String> = conn.query_row(
+        "SELECT hash FROM VoteEvent ORDER BY rowid DESC LIMIT 1",
+        [],
+        |row| row.get(0),
+    ).optional()?;
+
+    Ok(hash.unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+// SHA-256 of the previous hash and every stored field of the entry, the voter name left out since it lives on Voter.
+// Each field goes in with its length first so text can't be moved from one field to the next, and missing ones as ~
+pub fn event_hash(event: &VoteEvent) -> String {
+    let fields = [
+        Some(event.previous_hash.clone()),
+        Some(event.id.to_string()),
+        Some(event.vote_id.to_string()),
+        Some(event.poll_id.to_string()),
+        event.voter_id.map(|voter_id| voter_id.to_string()),
+        Some(event.action.to_string()),
+        event.choice.as_ref().map(|choice| choice.to_string()),
+        event.option_label.clone(),
+        event.comment.clone(),
+        event.previous_choice.as_ref().map(|choice| choice.to_string()),
+        event.previous_label.clone(),
+        event.previous_comment.clone(),
+        Some(event.voting_power.to_string()),
+        Some(event.event_date.to_string()),
+    ];
+
+    let mut hasher = Sha256::new();
+
+    for field in fields {
+        match field {
+            Some(value) => hasher.update(format!("{}:{};", value.len(), value)),
+            None => hasher.update("~;"),
+        }
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+// Whether the log still has the entry a head kept from an earlier report points at. The hashes chain every
+// entry to the ones before it, so if it does, nothing up to that entry was changed since
+pub fn has_head(conn: &Connection, head: &str) -> Result<bool> {
+    if head == GENESIS_HASH {
+        return Ok(true);
+    }
+
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM VoteEvent WHERE hash = ?1)",
+        [head.trim().to_lowercase()],
+        |row| row.get(0),
+    )
+}
+
+// Walks the log from the first entry, checking each one points at the one before and still has its hash.
+// Replaying it gives the votes that should exist, which are then compared with the Vote table.
+// That shows the log and the votes agree, not that nobody edited the file: the hashes are plain SHA-256, so
+// whoever can write to it can also write a new chain. Keeping the head outside the database and checking it
+// with has_head is what shows the entries up to it weren't touched
+pub fn verify_ledger(conn: &Connection) -> Result<LedgerReport> {
+    let events = vote::get_events(conn)?;
+    let head = last_hash(conn)?;
+    let mut expected_hash = GENESIS_HASH.to_string();
+    let mut live: HashMap<Uuid, (usize, &VoteEvent)> = HashMap::new();
+
+    for (i, event) in events.iter().enumerate() {
+        let issue = if event.previous_hash != expected_hash {
+            Some(LedgerIssue::BrokenChain)
+        } else if event_hash(event) != event.hash {
+            Some(LedgerIssue::HashMismatch)
+        } else {
+            None
+        };
+
+        if let Some(issue) = issue {
+            return Ok(LedgerReport {
+                entries: events.len(),
+                head,
+                problem: Some(LedgerProblem { entry: Some(i + 1), event_id: Some(event.id), vote_id: event.vote_id, issue }),
+            });
+        }
+
+        expected_hash = event.hash.clone();
+
+        match event.action {
+            VoteAction::Cast | VoteAction::Changed => live.insert(event.vote_id, (i + 1, event)),
+            VoteAction::Retracted => live.remove(&event.vote_id),
+        };
+    }
+
+    let votes = vote::get_votes(conn)?;
+
+    let mut logged: Vec<&(usize, &VoteEvent)> = live.values().collect();
+    logged.sort_by_key(|(entry, _)| *entry);
+
+    // Choices are compared as the text the log keeps them as, which reads a ranking or approval of one option
+    // back as a single choice
+    for (entry, event) in logged {
+        let issue = match votes.iter().find(|vote| vote.id == event.vote_id) {
+            None => Some(LedgerIssue::VoteMissing),
+            Some(vote) if event.choice.as_ref().map(|choice| choice.to_string()) != Some(vote.choice.to_string())
+                || event.comment.as_ref() != Some(&vote.comment)
+                || event.voting_power != vote.voting_power
+                || event.poll_id != vote.poll_id
+                || event.voter_id != vote.voter_id => Some(LedgerIssue::VoteDiffers),
+            Some(_) => None,
+        };
+
+        if let Some(issue) = issue {
+            return Ok(LedgerReport {
+                entries: events.len(),
+                head,
+                problem: Some(LedgerProblem { entry: Some(*entry), event_id: Some(event.id), vote_id: event.vote_id, issue }),
+            });
+        }
+    }
+
+    let problem = votes
+        .iter()
+        .find(|vote| !live.contains_key(&vote.id))
+        .map(|vote| LedgerProblem { entry: None, event_id: None, vote_id: vote.id, issue: LedgerIssue::VoteNotLogged });
+
+    Ok(LedgerReport { entries: events.len(), head, problem })
+}
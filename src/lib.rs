@@ -0,0 +1,198 @@
+//! Polls, voters and votes stored in SQLite, plus an HTTP handler for them.
+//! Nothing here prints or reads stdin, that is left to the binary.
+
+use rusqlite::{Connection, Result};
+
+pub mod error;
+pub mod poll;
+pub mod vote;
+pub mod voter;
+pub mod user;
+pub mod server;
+pub mod tally;
+pub mod ledger;
+pub mod permission;
+mod migration;
+mod tests;
+
+pub use error::PollError;
+pub use poll::{Outcome, Poll, PollDuration, PollKind, PollOption, PollRevision, PollRules, PollStatus, Threshold};
+pub use vote::{Vote, VoteAction, VoteChoice, VoteEvent};
+pub use user::{Role, User};
+pub use voter::Voter;
+
+// Creates the tables when they don't exist yet and upgrades the ones made by older versions, so it is safe
+// to call on every start
+pub fn create_tables(conn: &Connection) -> Result<()> {
+    let new_database = !migration::has_table(conn, "Poll")?;
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Poll (
+             id TEXT PRIMARY KEY,
+             question TEXT NOT NULL,
+             poll_duration TEXT NOT NULL,
+             create_date DATE NOT NULL,
+             start_date DATE NOT NULL,
+             expiration_date DATE NOT NULL,
+             abstentions INTEGER NOT NULL DEFAULT 0,
+             quorum INTEGER NOT NULL DEFAULT 0,
+             threshold TEXT NOT NULL DEFAULT 'majority',
+             kind TEXT NOT NULL DEFAULT 'single',
+             status TEXT NOT NULL DEFAULT 'open',
+             status_reason TEXT NOT NULL DEFAULT '',
+             anonymous INTEGER NOT NULL DEFAULT 0,
+             creator_id TEXT REFERENCES User(id) -- Empty on polls made before there were users
+             )",
+             (),
+            )?;
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS PollOption (
+             id TEXT PRIMARY KEY,
+             poll_id TEXT NOT NULL REFERENCES Poll(id),
+             label TEXT NOT NULL,
+             position INTEGER NOT NULL,
+             votes INTEGER NOT NULL
+         )",
+        (),
+    )?;
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Voter (
+             id TEXT PRIMARY KEY,
+             name TEXT NOT NULL UNIQUE,
+             create_date DATE NOT NULL,
+             weight INTEGER NOT NULL DEFAULT 1
+         )",
+        (),
+    )?;
+
+    // Accounts that can log in, each tied to the voter it votes as. The password is kept as an argon2 hash
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS User (
+             id TEXT PRIMARY KEY,
+             name TEXT NOT NULL UNIQUE,
+             password_hash TEXT NOT NULL,
+             create_date DATE NOT NULL,
+             voter_id TEXT NOT NULL UNIQUE REFERENCES Voter(id),
+             role TEXT NOT NULL DEFAULT 'voter'
+         )",
+        (),
+    )?;
+
+    // Lets a voter without an account sign up as that voter, keeping their weight and votes. The code is
+    // handed to them and only kept as a hash
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Invite (
+             voter_id TEXT PRIMARY KEY REFERENCES Voter(id),
+             code_hash TEXT NOT NULL UNIQUE,
+             create_date DATE NOT NULL
+         )",
+        (),
+    )?;
+
+    // API logins, found by a hash of their token
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Session (
+             token_hash TEXT PRIMARY KEY,
+             user_id TEXT NOT NULL REFERENCES User(id),
+             create_date DATE NOT NULL,
+             expiration_date DATE NOT NULL
+         )",
+        (),
+    )?;
+
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Vote (
+             id TEXT PRIMARY KEY,
+             choice TEXT NOT NULL, -- A PollOption id or 'abstain'
+             comment TEXT,
+             voting_power INTEGER NOT NULL,
+             create_date DATE NOT NULL,
+             poll_id TEXT NOT NULL REFERENCES Poll(id),
+             voter_id TEXT REFERENCES Voter(id), -- Empty on anonymous polls
+             receipt_hash TEXT UNIQUE, -- SHA-256 of the receipt handed to the voter, which isn't kept
+             UNIQUE (poll_id, voter_id)
+         )",
+        (),
+    )?;
+
+    // Who voted on each anonymous poll, kept apart from the votes. Without a rowid the rows are stored by
+    // voter, so their order can't be matched against the order the votes were cast in
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS Participation (
+             poll_id TEXT NOT NULL REFERENCES Poll(id),
+             voter_id TEXT NOT NULL REFERENCES Voter(id),
+             ballot_hash TEXT NOT NULL UNIQUE, -- Another hash of the receipt, which finds the row when the vote is retracted
+             PRIMARY KEY (poll_id, voter_id)
+         ) WITHOUT ROWID",
+        (),
+    )?;
+
+    // Append-only log of every vote cast, changed or retracted, each entry hashing the one before. It has no
+    // references to Vote or Poll, since it keeps the history of votes and polls that were deleted
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS VoteEvent (
+             id TEXT PRIMARY KEY,
+             vote_id TEXT NOT NULL,
+             poll_id TEXT NOT NULL,
+             voter_id TEXT REFERENCES Voter(id), -- Empty on anonymous polls
+             action TEXT NOT NULL,
+             choice TEXT,
+             option_label TEXT,
+             comment TEXT,
+             previous_choice TEXT,
+             previous_label TEXT,
+             previous_comment TEXT,
+             voting_power INTEGER NOT NULL,
+             event_date DATE NOT NULL,
+             previous_hash TEXT NOT NULL,
+             hash TEXT NOT NULL
+         )",
+        (),
+    )?;
+
+    // What a poll looked like before each edit, and who edited it
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS PollRevision (
+             id TEXT PRIMARY KEY,
+             poll_id TEXT NOT NULL REFERENCES Poll(id),
+             question TEXT NOT NULL,
+             poll_duration TEXT NOT NULL,
+             expiration_date DATE NOT NULL,
+             edit_date DATE NOT NULL,
+             editor_id TEXT REFERENCES User(id) -- Empty on edits by voters that never got an account
+         )",
+        (),
+    )?;
+
+    // One row per option of a ranked, approval or score ballot, in the order the voter gave them.
+    // On ranked polls rank 1 is the first preference, on score polls each option also has its score
+    conn.execute(
+        "
+        CREATE TABLE IF NOT EXISTS BallotEntry (
+             vote_id TEXT NOT NULL REFERENCES Vote(id),
+             option_id TEXT NOT NULL REFERENCES PollOption(id),
+             rank INTEGER NOT NULL,
+             score INTEGER,
+             PRIMARY KEY (vote_id, rank)
+         )",
+        (),
+    )?;
+
+    if new_database {
+        migration::mark_latest(conn)
+    } else {
+        migration::migrate(conn)
+    }
+}
@@ -1,44 +1,136 @@
 use rusqlite::{Connection, Result};
 use std::io;
+use std::process;
 use chrono::{Local,TimeZone};
+use clap::Parser;
 use uuid::Uuid;
 
-mod vote;
-mod poll;
-mod tests;
-
-fn create_tables(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "
-        CREATE TABLE IF NOT EXISTS Poll (
-             id TEXT PRIMARY KEY,
-             question TEXT NOT NULL,
-             poll_duration INTEGER NOT NULL,
-             create_date DATE NOT NULL,
-             expiration_date DATE NOT NULL,
-             positive_votes INTEGER NOT NULL,
-             negative_votes INTEGER NOT NULL
-             )",
-             (),
-            )?;
-
-    conn.execute(
-        "
-        CREATE TABLE IF NOT EXISTS Vote (
-             id TEXT PRIMARY KEY,
-             choice TEXT NOT NULL,
-             comment TEXT,
-             voting_power INTEGER NOT NULL,
-             create_date DATE NOT NULL,
-             poll_id TEXT NOT NULL REFERENCES Poll(id)
-         )",
-        (),
-    )?;
+use rust_trabalho::permission::{self, Action};
+use rust_trabalho::{create_tables, ledger, poll, user, vote, PollError, Role, User};
 
-    Ok(())
+mod cli;
+
+// Picks an item by its number in the list the user saw or by the start of its id (the short code)
+fn select<'a, T>(items: &'a [T], input: &str, id: impl Fn(&T) -> Uuid) -> Option<&'a T> {
+    let input = input.trim().to_lowercase();
+
+    if let Ok(num) = input.parse::<usize>() {
+        if num > 0 && num <= items.len() {
+            return Some(&items[num - 1]);
+        }
+    }
+
+    if input.len() < poll::MIN_SHORT_CODE_LEN {
+        return None;
+    }
+
+    let mut found = items.iter().filter(|item| id(item).to_string().starts_with(&input));
+
+    match (found.next(), found.next()) {
+        (Some(item), None) => Some(item),
+        _ => None,
+    }
+}
+
+// How to write a vote on polls that take more than one option
+fn print_ballot_hint(kind: poll::PollKind) {
+    match kind {
+        poll::PollKind::Single => {}
+        poll::PollKind::Ranked => println!("Rank the options you like in order, separated by commas (like 2,1,3)"),
+        poll::PollKind::Approval => println!("Write every option you approve of, separated by commas (like 1,3)"),
+        poll::PollKind::Score => println!("Score every option from 0 to {} in order, separated by commas (like 5,0,3)", vote::MAX_SCORE),
+    }
+}
+
+// Prints why the user can't go on, if they can't
+fn allowed(check: Result<(), PollError>) -> bool {
+    match check {
+        Ok(()) => true,
+        Err(err) => {
+            println!("\n{}", err);
+            false
+        }
+    }
+}
+
+// Logs in or creates an account before the menu is shown, asking again until one of them works
+fn ask_login(conn: &Connection) -> Result<User, Box<dyn std::error::Error>> {
+    loop {
+        println!("\n1 - Log in");
+        println!("2 - Create an account");
+        println!("3 - Create an account with an invite");
+
+        let mut answer = String::new();
+        let mut name = String::new();
+
+        io::stdin()
+            .read_line(&mut answer)
+            .expect("Error");
+
+        if !["1", "2", "3"].contains(&answer.trim()) {
+            println!("\nInvalid input, please try again.");
+            continue;
+        }
+
+        if answer.trim() == "3" {
+            println!("\nInvite code:");
+        } else {
+            println!("\nUser name:");
+        }
+
+        io::stdin()
+            .read_line(&mut name)
+            .expect("Failed to read user name");
+
+        let password = rpassword::prompt_password("Password: ")?;
+
+        let result = if answer.trim() == "1" {
+            user::login(conn, &name, &password)
+        } else if rpassword::prompt_password("Repeat the password: ")? != password {
+            println!("\nThe passwords don't match.");
+            continue;
+        } else if answer.trim() == "3" {
+            user::join(conn, &name, &password)
+        } else {
+            user::create_user(conn, name.trim().to_string(), &password)
+        };
+
+        match result {
+            Ok(user) => {
+                println!("\nLogged in as {}.", user.name);
+                return Ok(user);
+            }
+            Err(err) => println!("\n{}", err),
+        }
+    }
 }
 
-fn menu (conn: &Connection) -> Result<()>{
+// Asks for a receipt until it matches a vote, returning both. Leaving it empty goes back to the menu
+fn ask_receipt(conn: &Connection) -> Result<Option<(String, vote::Vote)>> {
+    let mut receipt = String::new();
+
+    loop {
+        println!("\nWrite the receipt you got when you voted, or leave it empty to go back:");
+        io::stdin()
+            .read_line(&mut receipt)
+            .expect("Failed to read receipt");
+
+        if receipt.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match vote::get_vote_by_receipt(conn, &receipt)? {
+            Some(vote) => return Ok(Some((receipt.trim().to_string(), vote))),
+            None => {
+                println!("\nNo vote has this receipt. Check the code and try again.");
+                receipt.clear();
+                continue;
+            }
+        }
+    }
+}
+
+fn menu (conn: &Connection, user: &User) -> Result<()>{
     loop {
         println!("\nWhat do you want to do?");
         println!("1 - Create a Poll");
@@ -49,7 +141,13 @@ fn menu (conn: &Connection) -> Result<()>{
         println!("6 - Delete a Vote");
         println!("7 - View Results");
         println!("8 - View Votes");
-        println!("9 - Exit");
+        println!("9 - Verify Vote Counters and Log");
+        println!("10 - Close or Reopen a Poll");
+        println!("11 - View Poll History");
+        println!("12 - View Vote Audit Trail");
+        println!("13 - Check a Vote Receipt");
+        println!("14 - Give a User a Role");
+        println!("15 - Exit");
 
         let mut answer = String::new();
 
@@ -60,9 +158,14 @@ fn menu (conn: &Connection) -> Result<()>{
         let answer = answer.trim();
 
         if answer == "1" {
+            if !allowed(permission::check(user, Action::CreatePoll)) {
+                let _ = menu(conn, user);
+                break;
+            }
+
             let mut question = String::new();
-            let mut input_days = String::new();
-            let poll_duration;
+            let mut input_start = String::new();
+            let mut input_duration = String::new();
 
             loop{
                 println!("\nWrite your question below:");
@@ -83,91 +186,238 @@ fn menu (conn: &Connection) -> Result<()>{
                 }
             }
 
+            loop {
+                println!("\nWhen should voting start? Leave empty to start now or write a date (2024-12-31 or 2024-12-31 18:00)");
+                io::stdin()
+                    .read_line(&mut input_start)
+                    .expect("Failed to read start date");
+
+                if input_start.trim().is_empty() || poll::parse_date(&input_start).is_some() {
+                    break;
+                }
+
+                println!("\nInvalid input. Please enter a date like 2024-12-31 or 2024-12-31 18:00.");
+                input_start.clear();
+            }
 
             loop {
-    
-                println!("\n7 days or 30 days until expiration?");
+                println!("\nHow long should the poll last? Write hours (36h), days (3d) or an end date (2024-12-31)");
                 io::stdin()
-                    .read_line(&mut input_days)
-                    .expect("Failed to read days");
+                    .read_line(&mut input_duration)
+                    .expect("Failed to read duration");
 
-                let input_days_trimmed = input_days.trim();
-                
-                poll_duration = match input_days_trimmed.parse::<i8>() {
-                    Ok(7) => {
-                        Some(poll::PollDuration::OneWeek)
-                    },
-                    Ok(30) => {
-                        Some(poll::PollDuration::OneMonth) 
+                match input_duration.parse::<poll::PollDuration>() {
+                    Ok(_) => break,
+                    Err(err) => {
+                        println!("\n{}", err);
+                        input_duration.clear();
+                        continue;
+                    }
+                }
+            }
+
+            let mut input_options = String::new();
+            let options: Vec<String>;
+
+            loop {
+                println!("\nWrite the options separated by commas (leave empty for Yes/No):");
+                io::stdin()
+                    .read_line(&mut input_options)
+                    .expect("Failed to read options");
+
+                if input_options.trim().is_empty() {
+                    options = poll::DEFAULT_OPTIONS.iter().map(|option| option.to_string()).collect();
+                    break;
+                }
+
+                let typed_options: Vec<String> = input_options.split(',').map(|option| option.trim().to_string()).collect();
+
+                if typed_options.len() < 2 || typed_options.iter().any(|option| option.is_empty()) {
+                    println!("\nInvalid input. Please enter at least 2 non empty options.");
+                    input_options.clear();
+                    continue;
+                }
+
+                options = typed_options;
+                break;
+            }
+
+            let mut input_quorum = String::new();
+            let quorum: i64;
+
+            loop {
+                println!("\nHow many votes are needed for the result to count? Leave empty for no quorum");
+                io::stdin()
+                    .read_line(&mut input_quorum)
+                    .expect("Failed to read quorum");
+
+                if input_quorum.trim().is_empty() {
+                    quorum = 0;
+                    break;
+                }
+
+                match input_quorum.trim().parse::<i64>() {
+                    Ok(num) if num >= 0 => {
+                        quorum = num;
+                        break;
                     }
                     _ => {
-                        println!("\nInvalid input. Please enter 7 or 30 Days.");
-                        input_days.clear();
+                        println!("\nInvalid input. Please enter a number of votes.");
+                        input_quorum.clear();
                         continue;
                     }
-                };   
-                break;
-            }      
+                }
+            }
+
+            let mut input_threshold = String::new();
+            let threshold: poll::Threshold;
+
+            loop {
+                println!("\nWhat share of the votes is needed to pass? Leave empty for a majority or write a fraction (2/3) or a percentage (60%)");
+                io::stdin()
+                    .read_line(&mut input_threshold)
+                    .expect("Failed to read threshold");
 
-            let _ = poll::create_poll(conn, question.to_string(), poll_duration.expect("to be 7 or 30").to_string());
-            let _ = menu(conn);
+                match input_threshold.parse::<poll::Threshold>() {
+                    Ok(parsed) => {
+                        threshold = parsed;
+                        break;
+                    }
+                    Err(err) => {
+                        println!("\n{}", err);
+                        input_threshold.clear();
+                        continue;
+                    }
+                }
+            }
+
+            let mut input_anonymous = String::new();
+            let anonymous: bool;
+
+            loop {
+                println!("\nShould the votes be secret? Nobody, not even the poll creator, will see who voted for what, and every vote counts once whatever the voter's weight (y/n)");
+                io::stdin()
+                    .read_line(&mut input_anonymous)
+                    .expect("Failed to read answer");
+
+                match input_anonymous.trim() {
+                    "y" => {
+                        anonymous = true;
+                        break;
+                    }
+                    "n" => {
+                        anonymous = false;
+                        break;
+                    }
+                    _ => {
+                        println!("\nInvalid input. Please answer y or n.");
+                        input_anonymous.clear();
+                        continue;
+                    }
+                }
+            }
+
+            let rules = poll::PollRules { quorum, threshold, anonymous };
+
+            let mut input_kind = String::new();
+            let kind;
+
+            loop {
+                println!("\nHow should voters answer? Leave empty for a single option, or write ranked, approval or score (0 to {} for each option)", vote::MAX_SCORE);
+                io::stdin()
+                    .read_line(&mut input_kind)
+                    .expect("Failed to read poll kind");
+
+                match input_kind.parse::<poll::PollKind>() {
+                    Ok(parsed) => {
+                        kind = parsed;
+                        break;
+                    }
+                    Err(err) => {
+                        println!("\n{}", err);
+                        input_kind.clear();
+                        continue;
+                    }
+                }
+            }
+
+            match poll::create_poll(conn, question.to_string(), input_duration.to_string(), options, input_start.to_string(), rules, kind, Some(user)) {
+                Ok(_) => println!("\nPoll Created!"),
+                Err(err) => println!("\n{}", err),
+            }
+            let _ = menu(conn, user);
             break;
         } else if answer == "2" {
-            let polls = poll::get_polls(conn)?;
+            if !allowed(permission::check(user, Action::CastVote)) {
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let polls: Vec<poll::Poll> = poll::get_polls(conn)?.into_iter().filter(|poll| poll.is_open()).collect();
 
             let mut choice = String::new();
             let mut vote = String::new();
             let mut answer = String::new();
             let mut comment = String::new();
+            let vote_choice;
 
             if polls.is_empty() {
-                println!("\nThere are no polls to vote.");
-                let _ = menu(conn);
+                println!("\nThere are no open polls to vote.");
+                let _ = menu(conn, user);
                 return Ok(());
             }
-    
+
+            let voter = user::user_voter(conn, user)?;
+
             println!("\nChoose one of the following polls:");
         
             for (i, poll) in polls.iter().enumerate() {
-                println!("{}. {}", i + 1, poll.question);
+                println!("{}. [{}] {}", i + 1, poll.short_code(), poll.question);
             }
         
-            loop{
+            let poll = loop{
                 io::stdin()
                 .read_line(&mut choice)
                 .expect("\nFailed to read line");
             
-                let _: usize = match choice.trim().parse() {
-                    Ok(num) if num > 0 && num <= polls.len() => num,
-                    _ => {
-                        println!("\nInvalid input. Please enter a number.");
+                match select(&polls, &choice, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("\nInvalid input. Please enter a number or a poll code.");
                         choice.clear();
                         continue;
                     }
                 };
-                break;
+            };
+
+            if vote::has_voted(conn, &poll.id, &voter.id)? {
+                println!("\n{} already voted on this poll. You can edit your vote instead.", voter.name);
+                let _ = menu(conn, user);
+                return Ok(());
             }
-            let choice: usize = choice.trim().parse().unwrap();
-    
-            let poll = &polls[choice - 1];
 
             loop{
-                println!("\nYou vote? (y/n)");    
-                println!("Digit 'y' for yes and 'n' for no");
+                println!("\nWhat is your vote?");
+
+                for (i, option) in poll.options.iter().enumerate() {
+                    println!("{} - {}", i + 1, option.label);
+                }
+
+                println!("{} - {}", poll.options.len() + 1, vote::ABSTAIN_LABEL);
+
+                print_ballot_hint(poll.kind);
         
                 io::stdin()
                     .read_line(&mut vote)
                     .expect("Error");
 
-                    match vote.trim() {
-                    "y" => {
-                        break;
-                    }
-                    "n" => {
+                match poll.find_choice(&vote) {
+                    Some(found_choice) => {
+                        vote_choice = found_choice;
                         break;
                     }
-                    _ => {
-                        println!("\nInvalid input. Please enter 'y' or 'n'.");
+                    None => {
+                        println!("\nInvalid input. Please enter one of the options.");
                         vote.clear();
                         continue;
                     }
@@ -208,67 +458,58 @@ fn menu (conn: &Connection) -> Result<()>{
                 }
             }
 
-            let _ = vote::create_vote(conn, poll.clone(), &vote, comment);
+            match vote::create_vote(conn, poll.clone(), &voter, vote_choice, comment) {
+                Ok(vote) => {
+                    println!("\nYour vote was registered successfully!");
+                    println!("Your receipt is: {}", vote.receipt.unwrap_or_default());
+                    println!("Keep it to check, change or retract your vote later. It won't be shown again.");
+                }
+                Err(err) => println!("\n{}", err),
+            }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
 
             break;
         } else if answer == "3" {
             let mut new_question = String::new();
-            let mut  input_days = String::new();
+            let mut input_duration = String::new();
             let _create_date: i64;
             let _expiration_date: i64;
             let mut choice1 = String::new();
             let mut choice2 = String::new();
 
-            let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes FROM Poll")?;
-            let poll_iter = stmt.query_map([], |row| {
-                Ok(poll::Poll {
-                    id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-                    question: row.get(1)?,
-                    poll_duration: row.get(2)?,
-                    create_date: row.get(3)?,
-                    expiration_date: row.get(4)?,
-                    positive_votes: row.get(5)?,
-                    negative_votes: row.get(6)?,
-                })
-            })?;
-            let mut polls = Vec::new();
-        
-            for poll in poll_iter {
-                polls.push(poll?);
-            }
+            let polls = poll::get_polls(conn)?;
     
             if polls.is_empty() {
                 println!("\nThere are no polls to Edit.");
-                let _ = menu(conn);
+                let _ = menu(conn, user);
                 return Ok(());
             }
             
-            loop{
+            let selected_poll = loop{
                 println!("\nChose one poll to edit:");
     
                 for (i, poll) in polls.iter().enumerate() {
-                    println!("{} - {}", i + 1, poll.question);
+                    println!("{} - [{}] {}", i + 1, poll.short_code(), poll.question);
                 }
                 
                 
                 io::stdin().read_line(&mut choice1).expect("Failed to read the choice");
     
-                let _choice1: usize = match choice1.trim().parse() {
-                    Ok(num) if num > 0 && num <= polls.len() => num,
-                    _ => {
-                        println!("\nInvalid input. Please enter a valid number.");
+                match select(&polls, &choice1, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("\nInvalid input. Please enter a valid number or poll code.");
                         choice1.clear();
                         continue;
                     }
                 };
-                break;
-            }
-
-            let choice1: usize = choice1.trim().parse().unwrap();
+            };
 
-            let _selected_poll = &polls[choice1 - 1];
+            if !allowed(permission::check_poll(user, selected_poll)) {
+                let _ = menu(conn, user);
+                return Ok(());
+            }
 
             loop{
                 println!("\nWrite your question below:");
@@ -300,28 +541,19 @@ fn menu (conn: &Connection) -> Result<()>{
                 } else if choice2.trim() == "y"{ 
 
                     loop {
-    
-                        println!("\n7 days or 30 days until expiration?");
+                        println!("\nHow long should the poll last? Write hours (36h), days (3d) or an end date (2024-12-31)");
                         io::stdin()
-                            .read_line(&mut input_days)
-                            .expect("Failed to read days");
+                            .read_line(&mut input_duration)
+                            .expect("Failed to read duration");
     
-                        let input_days_trimmed = input_days.trim();
-                        
-                        match input_days_trimmed.parse::<i8>() {
-                            Ok(7) => {
-                                Some(poll::PollDuration::OneWeek)
-                            },
-                            Ok(30) => {
-                                Some(poll::PollDuration::OneMonth) 
-                            }
-                            _ => {
-                                println!("\nInvalid input. Please enter 7 or 30 Days.");
-                                input_days.clear();
+                        match input_duration.parse::<poll::PollDuration>() {
+                            Ok(_) => break,
+                            Err(err) => {
+                                println!("\n{}", err);
+                                input_duration.clear();
                                 continue;
                             }
-                        };   
-                        break; 
+                        }
                     }
                     break;
                 } 
@@ -330,64 +562,67 @@ fn menu (conn: &Connection) -> Result<()>{
                     choice2.clear();
                 }
             }
-            let _ = poll::edit_poll(conn, choice1.to_string(), choice2.to_string(), new_question.to_string(), input_days.to_string());
-            let _ = menu(conn);
+            match poll::edit_poll(conn, &selected_poll.id, user, choice2.to_string(), new_question.to_string(), input_duration.to_string()) {
+                Ok(poll) => println!("\nPoll {} edited Successfully", poll.short_code()),
+                Err(err) => println!("\n{}", err),
+            }
+            let _ = menu(conn, user);
             break;
         } else if answer == "4" {
-            let votes = vote::get_votes(conn)?;
+            let (receipt, selected_vote) = match ask_receipt(conn)? {
+                Some(found) => found,
+                None => {
+                    let _ = menu(conn, user);
+                    return Ok(());
+                }
+            };
 
-            if votes.is_empty() {
-                println!("\nThere are no votes to edit.");
-                let _ = menu(conn);
+            if !allowed(permission::check_vote(user, &selected_vote)) {
+                let _ = menu(conn, user);
                 return Ok(());
             }
-    
-            println!("\nChoose one of the following votes to edit:");
-    
-            for (i, vote) in votes.iter().enumerate() {
-                println!("{}. Vote: {} | Question: {} | Date: {}", i + 1, vote.choice, vote.poll_question, Local.timestamp_opt(vote.create_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"));
-            }
-    
-            let mut choice = String::new();
-    
-            loop{
-                io::stdin()
-                .read_line(&mut choice)
-                .expect("Failed to read line");
-            
-                let _: usize = match choice.trim().parse() {
-                    Ok(num) if num > 0 && num <= votes.len() => num,
-                    _ => {
-                        println!("\nInvalid input. Please enter a number.");
-                        choice.clear();
-                        continue;
-                    }
-                };
-                break;
-            }
-    
-            let choice: usize = choice.trim().parse().unwrap();
-    
-            let selected_vote = &votes[choice - 1];
-    
+
+            println!("\nYour vote: {} | Question: {}", selected_vote.option_label, selected_vote.poll_question);
+
             let mut new_choice = String::new();
             let mut new_comment = String::new();
             let mut answer = String::new();
+            let vote_choice;
+
+            let selected_poll = match poll::get_poll(conn, &selected_vote.poll_id)? {
+                Some(poll) => poll,
+                None => {
+                    println!("\nThe poll of this vote doesn't exist anymore.");
+                    let _ = menu(conn, user);
+                    return Ok(());
+                }
+            };
     
             loop{
-                println!("\nYou vote? (y/n)");    
-                println!("Digit 'y' for yes and 'n' for no");
+                println!("\nWhat is your vote?");
+
+                for (i, option) in selected_poll.options.iter().enumerate() {
+                    println!("{} - {}", i + 1, option.label);
+                }
+
+                println!("{} - {}", selected_poll.options.len() + 1, vote::ABSTAIN_LABEL);
+
+                print_ballot_hint(selected_poll.kind);
     
                 io::stdin()
                     .read_line(&mut new_choice)
                     .expect("Error");
                 
-                if new_choice.trim() == "y" || new_choice.trim() == "n" {
-                    break;
-                } else{
-                    println!("\nInvalid input. Please enter 'y' or 'n'.");
-                    new_choice.clear();
-                    continue;
+                match selected_poll.find_choice(&new_choice) {
+                    Some(found_choice) => {
+                        vote_choice = found_choice;
+                        break;
+                    }
+                    None => {
+                        println!("\nInvalid input. Please enter one of the options.");
+                        new_choice.clear();
+                        continue;
+                    }
                 }
             }
 
@@ -426,138 +661,99 @@ fn menu (conn: &Connection) -> Result<()>{
             }
 
     
-            let current_vote = &votes[choice - 1];
-
-            let _ = vote::edit_vote(conn, current_vote, selected_vote, new_choice, new_comment);
+            match vote::edit_vote_by_receipt(conn, &receipt, vote_choice, new_comment) {
+                Ok(_) => println!("\nYour vote was edited successfully!"),
+                Err(err) => println!("\n{}", err),
+            }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
 
             break;
         } else if answer == "5" {
             let mut choice = String::new();
             let mut confirmation = String::new();
     
-            let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes FROM Poll")?;
-            let poll_iter = stmt.query_map([], |row| {
-                Ok(poll::Poll {
-                    id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-                    question: row.get(1)?,
-                    poll_duration: row.get(2)?,
-                    create_date: row.get(3)?,
-                    expiration_date: row.get(4)?,
-                    positive_votes: row.get(5)?,
-                    negative_votes: row.get(6)?,
-                })
-            })?;
-            let mut polls = Vec::new();
-        
-            for poll in poll_iter {
-                polls.push(poll?);
-            }
+            let polls = poll::get_polls(conn)?;
             
             if polls.is_empty() {
                 println!("\nThere are no polls to Delete.");
-                let _ = menu(conn);
+                let _ = menu(conn, user);
                 return Ok(());
             }
     
-            loop{
+            let selected_poll = loop{
                 println!("\nChose one poll to delete:");
     
                 for (i, poll) in polls.iter().enumerate() {
-                    println!("{} - {}", i + 1, poll.question);
+                    println!("{} - [{}] {}", i + 1, poll.short_code(), poll.question);
                 }
                 
                 
                 io::stdin().read_line(&mut choice).expect("Failed to read the choice");
     
-                let _choice: usize = match choice.trim().parse() {
-                    Ok(num) if num > 0 && num <= polls.len() => num,
-                    _ => {
-                        println!("Invalid input. Please enter a valid number.");
+                match select(&polls, &choice, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("Invalid input. Please enter a valid number or poll code.");
                         choice.clear();
                         continue;
                     }
                 };
+            };
+    
+            if !allowed(permission::check_poll(user, selected_poll)) {
+                let _ = menu(conn, user);
                 break;
             }
-            let choice: usize = choice.trim().parse().unwrap();
-    
-            println!("\nAre you sure you want to delete the poll: '{}'? (y/n)", polls[choice - 1].question );
+
+            println!("\nAre you sure you want to delete the poll: '{}' [{}]? (y/n)", selected_poll.question, selected_poll.short_code());
             io::stdin()
                 .read_line(&mut confirmation)
                 .expect("Error");
     
             if confirmation.trim() == "y" {
-                //Check if some poll.id is the same as poll_id. If it is equal it returns the index position then remove the poll from the polls list.
-                if let Some(_index) = polls.iter().position(|poll| poll.id == polls[choice - 1].id) {
-                    let _ = poll::delete_poll(conn, choice.to_string(), confirmation.to_string());
-                } else {
-                    panic!("Error When Deleting the Poll")
+                match poll::delete_poll(conn, &selected_poll.id, confirmation.to_string()) {
+                    Ok(_) => println!("\nPoll Removed Successfuly!"),
+                    Err(err) => println!("\n{}", err),
                 }
             } else{
                 println!("\nCanceling operation")
             }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
             break;
         } else if answer == "6" {
-            let votes = vote::get_votes(conn)?;
+            let (receipt, selected_vote) = match ask_receipt(conn)? {
+                Some(found) => found,
+                None => {
+                    let _ = menu(conn, user);
+                    break;
+                }
+            };
 
-            if votes.is_empty() {
-                println!("\nThere are no votes to show.");
-                let _ = menu(conn);
+            if !allowed(permission::check_vote(user, &selected_vote)) {
+                let _ = menu(conn, user);
                 break;
             }
 
-            println!("\nChoose one of the following votes to delete:");
-
-            for (i, vote) in votes.iter().enumerate() {
-                println!("{}. Vote: {} | Question: {} | Date: {}", i + 1, vote.choice, vote.poll_question, Local.timestamp_opt(vote.create_date, 0).unwrap().format("%d-%m-%Y %H:%M:%S"));
-            }
-
-            let mut choice = String::new();
             let mut confirmation = String::new();
-                
-            loop{
-                io::stdin()
-                .read_line(&mut choice)
-                .expect("Failed to read line");
-            
-                let _: usize = match choice.trim().parse() {
-                    Ok(num) if num > 0 && num <= votes.len() => num,
-                    _ => {
-                        println!("\nInvalid input. Please enter a number.");
-                        choice.clear();
-                        continue;
-                    }
-                };
-                break;
-            }
-    
-            let choice: usize = choice.trim().parse().unwrap();
-    
-            let selected_vote = &votes[choice - 1];
-    
-            println!("\nAre you sure you want to delete the vote: {} - '{}'? (y/n)", votes[choice - 1].choice, votes[choice - 1].poll_question);
+
+            println!("\nAre you sure you want to delete the vote: {} - '{}' [{}]? (y/n)", selected_vote.option_label, selected_vote.poll_question, selected_vote.short_code());
     
             io::stdin()
                 .read_line(&mut confirmation)
                 .expect("Error");
     
             if confirmation.trim() == "y" {
-                if let Some(_index) = votes.iter().position(|poll| poll.id == votes[choice - 1].id) {
-
-                    vote::delete_vote(conn, selected_vote)?;
-        
-                } else {
-                    panic!("Error when deleting the vote")
+                match vote::delete_vote_by_receipt(conn, &receipt) {
+                    Ok(_) => println!("\nYour vote was removed successfully!"),
+                    Err(err) => println!("\n{}", err),
                 }
             } else{
                 println!("\nCanceling operation")
             }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
 
             break;
         } else if answer == "7" {
@@ -565,25 +761,15 @@ fn menu (conn: &Connection) -> Result<()>{
 
             if polls.is_empty() {
                 println!("\nThere are no polls to show.");
-                let _ = menu(conn);
+                let _ = menu(conn, user);
                 break;
             }
 
             for poll in polls {
-                
-                let create_date = Local.timestamp_opt(poll.create_date, 0).unwrap();
-                let expiration_date = Local.timestamp_opt(poll.expiration_date, 0).unwrap();
-
-                println!("\nQuestion: {} \nPositive Votes: {}\nNegative Votes: {} \nCreate Date: {}\nExpiration Date: {} \nTotal Poll Duration: {} Days",
-                poll.question, 
-                poll.positive_votes,
-                poll.negative_votes,
-                create_date.format("%d-%m-%Y %H:%M:%S"),
-                expiration_date.format("%d-%m-%Y %H:%M:%S"),
-                poll.poll_duration);
+                cli::print_poll_results(&poll);
             }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
 
             break;
         } else if answer == "8" {
@@ -591,7 +777,7 @@ fn menu (conn: &Connection) -> Result<()>{
 
             if votes.is_empty() {
                 println!("\nThere are no votes to show.");
-                let _ = menu(conn);
+                let _ = menu(conn, user);
                 break;
             }
 
@@ -599,14 +785,249 @@ fn menu (conn: &Connection) -> Result<()>{
                 
                 let create_date = Local.timestamp_opt(vote.create_date, 0).unwrap();
 
-                println!("\nQuestion: {} \nChoice: {} \nComment: {} \nCreate Date: {}", vote.poll_question, vote.choice, vote.comment, create_date.format("%d-%m-%Y %H:%M:%S"));
+                println!("\nQuestion: {} \nVoter: {} \nChoice: {} \nComment: {} \nCreate Date: {}", vote.poll_question, vote.voter_name, vote.option_label, vote.comment, create_date.format("%d-%m-%Y %H:%M:%S"));
             }
 
-            let _ = menu(conn);
+            let _ = menu(conn, user);
 
             break;
         } 
         else if answer == "9" {
+            let report = ledger::verify_ledger(conn)?;
+
+            match report.problem {
+                Some(problem) => println!("\nThe vote log was tampered with. {}", problem),
+                None => println!("\nThe vote log is intact. Its head is {}, keep it somewhere else to check the log against later.", report.head),
+            }
+
+            let mismatches = poll::verify_tallies(conn, false)?;
+
+            if mismatches.is_empty() {
+                println!("\nAll vote counters match the votes.");
+                let _ = menu(conn, user);
+                break;
+            }
+
+            for mismatch in &mismatches {
+                println!("\nQuestion: {} \nOption: {} \nStored Votes: {} \nCounted Votes: {}", mismatch.question, mismatch.label, mismatch.stored_votes, mismatch.counted_votes);
+            }
+
+            if !allowed(permission::check(user, Action::ManageVoters)) {
+                println!("{} vote counters are wrong. Ask an admin to fix them.", mismatches.len());
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let mut confirmation = String::new();
+
+            println!("\n{} vote counters are wrong. Do you want to fix them? (y/n)", mismatches.len());
+            io::stdin()
+                .read_line(&mut confirmation)
+                .expect("Error");
+
+            if confirmation.trim() == "y" {
+                let repaired = poll::verify_tallies(conn, true)?;
+                println!("\n{} vote counters fixed.", repaired.len());
+            } else {
+                println!("\nCanceling operation");
+            }
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "10" {
+            let polls = poll::get_polls(conn)?;
+
+            if polls.is_empty() {
+                println!("\nThere are no polls to change.");
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let mut choice = String::new();
+            let mut input_status = String::new();
+            let mut reason = String::new();
+
+            let selected_poll = loop{
+                println!("\nChose one poll to close or reopen:");
+
+                for (i, poll) in polls.iter().enumerate() {
+                    println!("{} - [{}] ({}) {}", i + 1, poll.short_code(), poll.status, poll.question);
+                }
+
+                io::stdin().read_line(&mut choice).expect("Failed to read the choice");
+
+                match select(&polls, &choice, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("\nInvalid input. Please enter a valid number or poll code.");
+                        choice.clear();
+                        continue;
+                    }
+                };
+            };
+
+            if !allowed(permission::check_poll(user, selected_poll)) {
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let status = loop {
+                println!("\nWhat should the poll be? Write open, closed, draft or archived");
+                io::stdin()
+                    .read_line(&mut input_status)
+                    .expect("Failed to read status");
+
+                match input_status.parse::<poll::PollStatus>() {
+                    Ok(status) => break status,
+                    Err(err) => {
+                        println!("\n{}", err);
+                        input_status.clear();
+                        continue;
+                    }
+                }
+            };
+
+            println!("\nWhy? (optional)");
+            io::stdin()
+                .read_line(&mut reason)
+                .expect("Failed to read reason");
+
+            match poll::set_poll_status(conn, &selected_poll.id, status, reason.to_string()) {
+                Ok(poll) => println!("\nPoll {} is now {}", poll.short_code(), poll.status),
+                Err(err) => println!("\n{}", err),
+            }
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "11" {
+            let polls = poll::get_polls(conn)?;
+
+            if polls.is_empty() {
+                println!("\nThere are no polls to show.");
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let mut choice = String::new();
+
+            let selected_poll = loop{
+                println!("\nChose one poll to see its history:");
+
+                for (i, poll) in polls.iter().enumerate() {
+                    println!("{} - [{}] {}", i + 1, poll.short_code(), poll.question);
+                }
+
+                io::stdin().read_line(&mut choice).expect("Failed to read the choice");
+
+                match select(&polls, &choice, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("\nInvalid input. Please enter a valid number or poll code.");
+                        choice.clear();
+                        continue;
+                    }
+                };
+            };
+
+            cli::print_poll_history(selected_poll, &poll::get_poll_revisions(conn, &selected_poll.id)?);
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "12" {
+            let polls = poll::get_polls(conn)?;
+
+            if polls.is_empty() {
+                println!("\nThere are no polls to show.");
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let mut choice = String::new();
+
+            let selected_poll = loop{
+                println!("\nChose one poll to see every vote cast, changed or retracted on it:");
+
+                for (i, poll) in polls.iter().enumerate() {
+                    println!("{} - [{}] {}", i + 1, poll.short_code(), poll.question);
+                }
+
+                io::stdin().read_line(&mut choice).expect("Failed to read the choice");
+
+                match select(&polls, &choice, |poll| poll.id) {
+                    Some(poll) => break poll,
+                    None => {
+                        println!("\nInvalid input. Please enter a valid number or poll code.");
+                        choice.clear();
+                        continue;
+                    }
+                };
+            };
+
+            println!("\nQuestion: {}", selected_poll.question);
+            cli::print_vote_events(&vote::get_poll_events(conn, &selected_poll.id)?);
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "13" {
+            if let Some((_, vote)) = ask_receipt(conn)? {
+                let create_date = Local.timestamp_opt(vote.create_date, 0).unwrap();
+
+                println!("\nYour vote is counted.");
+                println!("Question: {} \nChoice: {} \nComment: {} \nCreate Date: {}", vote.poll_question, vote.option_label, vote.comment, create_date.format("%d-%m-%Y %H:%M:%S"));
+            }
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "14" {
+            if !allowed(permission::check(user, Action::ManageUsers)) {
+                let _ = menu(conn, user);
+                break;
+            }
+
+            let mut name = String::new();
+            let mut input_role = String::new();
+
+            println!("\nWhich user? Write their name:");
+            io::stdin()
+                .read_line(&mut name)
+                .expect("Failed to read user name");
+
+            let role = loop {
+                println!("\nWhat should their role be? Write admin, poll_manager, voter or observer");
+                io::stdin()
+                    .read_line(&mut input_role)
+                    .expect("Failed to read role");
+
+                match input_role.parse::<Role>() {
+                    Ok(role) => break role,
+                    Err(err) => {
+                        println!("\n{}", err);
+                        input_role.clear();
+                        continue;
+                    }
+                }
+            };
+
+            match user::set_user_role(conn, &name, role) {
+                Ok(changed) => println!("\n{} is now {}.", changed.name, changed.role),
+                Err(err) => println!("\n{}", err),
+            }
+
+            let _ = menu(conn, user);
+
+            break;
+        }
+        else if answer == "15" {
             println!("\nExiting...");
             break;
         } else {
@@ -617,12 +1038,46 @@ fn menu (conn: &Connection) -> Result<()>{
     Ok(())
 }
 fn main() -> Result<()> {
-    let conn = Connection::open("database.db")?;
+    let args = cli::Cli::parse();
+
+    let conn = Connection::open(&args.database)?;
     create_tables(&conn)?;
 
-    println!("Hello!");    
+    // Without a subcommand the interactive menu is used, as before
+    match args.command {
+        Some(command) => {
+            let user = match args.user {
+                Some(name) => match cli::login(&conn, &name) {
+                    Ok(user) => Some(user),
+                    Err(err) => {
+                        eprintln!("{}", err);
+                        process::exit(1);
+                    }
+                },
+                None => None,
+            };
+
+            if let Err(err) = cli::run(&conn, command, args.format, user.as_ref()) {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+        }
+        None => {
+            println!("Hello!");
+
+            let user = match args.user {
+                Some(name) => cli::login(&conn, &name),
+                None => ask_login(&conn),
+            };
 
-    let _ = menu(&conn);
+            match user {
+                Ok(user) => {
+                    let _ = menu(&conn, &user);
+                }
+                Err(err) => eprintln!("{}", err),
+            }
+        }
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,234 @@
+use rusqlite::{Connection, OptionalExtension, Result};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// Upgrades a database made by an older version, one step per change to the Poll and Vote tables it started
+// with, in the order they were made. PRAGMA user_version keeps how many steps a database has had, databases
+// from before it was kept being at 0. The other tables are made whole by create_tables.
+// Steps are plain SQL and never call the rest of the crate, so changing it later doesn't change what they do
+const MIGRATIONS: &[fn(&Connection) -> Result<()>] = &[
+    poll_options,
+    vote_voters,
+    poll_start_dates,
+    abstentions,
+    poll_rules,
+    poll_kinds,
+    poll_statuses,
+    logged_votes,
+    anonymous_polls,
+    vote_receipts,
+    poll_creators,
+];
+
+pub fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)",
+        [table],
+        |row| row.get(0),
+    )
+}
+
+// A new database already has the latest tables, so it is marked as having every step
+pub fn mark_latest(conn: &Connection) -> Result<()> {
+    conn.pragma_update(None, "user_version", MIGRATIONS.len() as i64)
+}
+
+// Runs the steps the database hasn't had yet, all of them or none
+pub fn migrate(conn: &Connection) -> Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if version >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    for migration in &MIGRATIONS[version..] {
+        migration(&tx)?;
+    }
+
+    mark_latest(&tx)?;
+
+    tx.commit()
+}
+
+// Yes/no polls kept their counters on Poll and each vote as "y" or "n". They become a Yes and a No option,
+// with the counters moved over and the votes pointing at them
+fn poll_options(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT id, positive_votes, negative_votes FROM Poll")?;
+    let polls = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?)))?.collect::<Result<Vec<_>>>()?;
+
+    for (poll_id, positive_votes, negative_votes) in polls {
+        for (position, (label, votes, choice)) in [("Yes", positive_votes, "y"), ("No", negative_votes, "n")].into_iter().enumerate() {
+            let option_id = Uuid::new_v4().to_string();
+
+            conn.execute(
+                "INSERT INTO PollOption (id, poll_id, label, position, votes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                (&option_id, &poll_id, label, position as i64, votes),
+            )?;
+
+            conn.execute(
+                "UPDATE Vote SET choice = ?1 WHERE poll_id = ?2 AND TRIM(choice) = ?3",
+                (&option_id, &poll_id, choice),
+            )?;
+        }
+    }
+
+    conn.execute_batch(
+        "
+        ALTER TABLE Vote RENAME COLUMN choice TO option_id;
+        ALTER TABLE Poll DROP COLUMN positive_votes;
+        ALTER TABLE Poll DROP COLUMN negative_votes;
+        ",
+    )
+}
+
+// Votes from before voters were tracked keep no voter. A column can't be added with a constraint, so the
+// one ballot per voter rule becomes an index
+fn vote_voters(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Vote ADD COLUMN voter_id TEXT REFERENCES Voter(id);
+        CREATE UNIQUE INDEX VoteVoter ON Vote (poll_id, voter_id);
+        ",
+    )
+}
+
+// Polls used to start when they were made. The duration column keeps the integer days it had, which
+// PollDuration still reads
+fn poll_start_dates(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Poll ADD COLUMN start_date DATE NOT NULL DEFAULT 0;
+        UPDATE Poll SET start_date = create_date;
+        ",
+    )
+}
+
+// The column with the option a vote is for went back to being called choice, since it can also be "abstain"
+fn abstentions(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Poll ADD COLUMN abstentions INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE Vote RENAME COLUMN option_id TO choice;
+        ",
+    )
+}
+
+fn poll_rules(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Poll ADD COLUMN quorum INTEGER NOT NULL DEFAULT 0;
+        ALTER TABLE Poll ADD COLUMN threshold TEXT NOT NULL DEFAULT 'majority';
+        ",
+    )
+}
+
+// Every poll before ranked ones was a single choice poll
+fn poll_kinds(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE Poll ADD COLUMN kind TEXT NOT NULL DEFAULT 'single'", ())?;
+
+    Ok(())
+}
+
+// Polls from before statuses are open, and the ones past their expiration date are still closed by it
+fn poll_statuses(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Poll ADD COLUMN status TEXT NOT NULL DEFAULT 'open';
+        ALTER TABLE Poll ADD COLUMN status_reason TEXT NOT NULL DEFAULT '';
+        ",
+    )
+}
+
+// The chained hash of a log entry as the vote log computed it when this step was written: SHA-256 of each
+// field with its length first, and missing ones as ~
+fn entry_hash(fields: &[Option<&str>]) -> String {
+    let mut hasher = Sha256::new();
+
+    for field in fields {
+        match field {
+            Some(value) => hasher.update(format!("{}:{};", value.len(), value)),
+            None => hasher.update("~;"),
+        }
+    }
+
+    format!("{:x}", hasher.finalize())
+}
+
+// Votes cast before there was a log get their cast entry, dated when they were cast, so the ledger check
+// doesn't find them missing from it. None of them can have a ballot of several options yet
+fn logged_votes(conn: &Connection) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "SELECT Vote.id, Vote.poll_id, voter_id, choice, COALESCE(label, 'Abstain'), comment, voting_power, Vote.create_date
+        FROM Vote LEFT JOIN PollOption ON Vote.choice = PollOption.id
+        WHERE NOT EXISTS(SELECT 1 FROM VoteEvent WHERE vote_id = Vote.id) ORDER BY Vote.rowid"
+    )?;
+    let votes = stmt.query_map([], |row| Ok((
+        row.get::<_, String>(0)?,
+        row.get::<_, String>(1)?,
+        row.get::<_, Option<String>>(2)?,
+        row.get::<_, String>(3)?,
+        row.get::<_, String>(4)?,
+        row.get::<_, String>(5)?,
+        row.get::<_, i64>(6)?,
+        row.get::<_, i64>(7)?,
+    )))?.collect::<Result<Vec<_>>>()?;
+
+    let mut previous_hash = conn.query_row("SELECT hash FROM VoteEvent ORDER BY rowid DESC LIMIT 1", [], |row| row.get::<_, String>(0))
+        .optional()?
+        .unwrap_or_else(|| "0".repeat(64));
+
+    for (vote_id, poll_id, voter_id, choice, option_label, comment, voting_power, create_date) in votes {
+        let event_id = Uuid::new_v4().to_string();
+        let hash = entry_hash(&[
+            Some(&previous_hash),
+            Some(&event_id),
+            Some(&vote_id),
+            Some(&poll_id),
+            voter_id.as_deref(),
+            Some("cast"),
+            Some(&choice),
+            Some(&option_label),
+            Some(&comment),
+            None,
+            None,
+            None,
+            Some(&voting_power.to_string()),
+            Some(&create_date.to_string()),
+        ]);
+
+        conn.execute(
+            "INSERT INTO VoteEvent (id, vote_id, poll_id, voter_id, action, choice, option_label, comment, voting_power, event_date, previous_hash, hash)
+            VALUES (?1, ?2, ?3, ?4, 'cast', ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            rusqlite::params![event_id, vote_id, poll_id, voter_id, choice, option_label, comment, voting_power, create_date, previous_hash, hash],
+        )?;
+
+        previous_hash = hash;
+    }
+
+    Ok(())
+}
+
+fn anonymous_polls(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE Poll ADD COLUMN anonymous INTEGER NOT NULL DEFAULT 0", ())?;
+
+    Ok(())
+}
+
+// Votes cast before receipts have none, so they can't be looked up by one
+fn vote_receipts(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "
+        ALTER TABLE Vote ADD COLUMN receipt_hash TEXT;
+        CREATE UNIQUE INDEX VoteReceipt ON Vote (receipt_hash);
+        ",
+    )
+}
+
+// Polls made before there were users have no creator
+fn poll_creators(conn: &Connection) -> Result<()> {
+    conn.execute("ALTER TABLE Poll ADD COLUMN creator_id TEXT REFERENCES User(id)", ())?;
+
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use crate::error::PollError;
+use crate::poll::Poll;
+use crate::user::{Role, User};
+use crate::vote::Vote;
+
+// Everything that needs more than reading. The menu, the CLI and the server all check these before calling
+// the poll, vote and user functions, which don't check anything themselves
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Action {
+    CreatePoll,
+    ManagePoll, // Edit, delete, close or reopen a poll
+    CastVote,
+    ChangeVote, // Edit or retract a vote
+    ManageVoters, // Set voter weights and repair vote counters
+    ManageUsers, // Give users their roles and invite voters to sign up
+}
+
+impl Action {
+    // Shown after "Permission denied."
+    pub fn who_can(&self) -> &'static str {
+        match self {
+            Action::CreatePoll => "Only admins and poll managers can create polls.",
+            Action::ManagePoll => "Only the poll manager who created this poll or an admin can change or delete it.",
+            Action::CastVote => "Observers can't vote.",
+            Action::ChangeVote => "Only whoever cast this vote or an admin can change or retract it.",
+            Action::ManageVoters => "Only admins can set voter weights or repair vote counters.",
+            Action::ManageUsers => "Only admins can give out roles and invites.",
+        }
+    }
+}
+
+impl Role {
+    pub fn allows(&self, action: Action) -> bool {
+        match self {
+            Role::Admin => true,
+            Role::PollManager => matches!(action, Action::CreatePoll | Action::ManagePoll | Action::CastVote | Action::ChangeVote),
+            Role::Voter => matches!(action, Action::CastVote | Action::ChangeVote),
+            Role::Observer => false,
+        }
+    }
+}
+
+// Actions that aren't about one poll or vote only depend on the role
+pub fn check(user: &User, action: Action) -> Result<(), PollError> {
+    if user.role.allows(action) {
+        Ok(())
+    } else {
+        Err(PollError::PermissionDenied(action))
+    }
+}
+
+// Poll managers can only manage the polls they created. Polls from before there were users are left to admins
+pub fn check_poll(user: &User, poll: &Poll) -> Result<(), PollError> {
+    check(user, Action::ManagePoll)?;
+
+    if user.role == Role::Admin || poll.creator_id == Some(user.id) {
+        Ok(())
+    } else {
+        Err(PollError::PermissionDenied(Action::ManagePoll))
+    }
+}
+
+// Votes of anonymous polls have no voter to compare with. They can only be reached with their receipt,
+// which already shows they belong to whoever has it
+pub fn check_vote(user: &User, vote: &Vote) -> Result<(), PollError> {
+    check(user, Action::ChangeVote)?;
+
+    if user.role == Role::Admin || vote.voter_id.is_none_or(|voter_id| voter_id == user.voter_id) {
+        Ok(())
+    } else {
+        Err(PollError::PermissionDenied(Action::ChangeVote))
+    }
+}
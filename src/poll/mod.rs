@@ -1,358 +1,1089 @@
-use rusqlite::{Connection, Result, types::ToSqlOutput, ToSql, types::FromSqlError, types::ValueRef, types::FromSql};
+use rusqlite::{Connection, OptionalExtension, Result, types::ToSqlOutput, ToSql, types::FromSqlError, types::ValueRef, types::FromSql};
 use std::fmt;
-use chrono::Local;
+use std::str::FromStr;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
+use serde::{Serialize, Serializer};
 use uuid::Uuid;
-use std::error::Error;
+use crate::error::PollError;
+use crate::tally::{self, Round};
+use crate::vote::{self, VoteChoice, ABSTAIN_LABEL};
+use crate::user::User;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub struct Poll {
    pub id: Uuid, //Could have used a sequential one but I find it easier
    pub question: String,
    pub poll_duration: PollDuration,
+   #[serde(serialize_with = "serialize_date")]
    pub create_date: i64,
+   #[serde(serialize_with = "serialize_date")]
+   pub start_date: i64,
+   #[serde(serialize_with = "serialize_date")]
    pub expiration_date: i64,
-   pub positive_votes: i16,
-   pub negative_votes: i16,
+   pub options: Vec<PollOption>,
+   pub abstentions: i64, // Voting power of the votes that abstained
+   pub ballots: i64, // Voting power of the votes that didn't abstain, counted when it is loaded
+   #[serde(flatten)]
+   pub rules: PollRules,
+   pub kind: PollKind,
+   pub status: PollStatus,
+   #[serde(skip_serializing_if = "String::is_empty")]
+   pub status_reason: String, // Why the status was last changed, if a reason was given
+   #[serde(skip_serializing_if = "Option::is_none")]
+   pub creator_id: Option<Uuid>, // The user who created it, unknown on polls made before there were users
+   #[serde(skip_serializing_if = "Vec::is_empty")]
+   pub runoff: Vec<Round>, // Instant runoff rounds of a ranked poll, counted when it is loaded
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
-pub enum PollDuration {
-    OneWeek = 7,
-    OneMonth = 30,
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PollOption {
+   pub id: Uuid,
+   pub poll_id: Uuid,
+   pub label: String,
+   pub votes: i64,
+}
+
+// A poll as it was before one of its edits. The question and duration are the old ones
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct PollRevision {
+   pub id: Uuid,
+   pub poll_id: Uuid,
+   pub question: String,
+   pub poll_duration: PollDuration,
+   #[serde(serialize_with = "serialize_date")]
+   pub expiration_date: i64,
+   #[serde(serialize_with = "serialize_date")]
+   pub edit_date: i64,
+   pub editor_name: String,
+}
+
+// An option, or the abstentions of a poll, whose stored counter doesn't match the votes in the Vote table
+#[derive(Debug, PartialEq, Clone)]
+pub struct TallyMismatch {
+   pub poll_id: Uuid,
+   pub question: String,
+   pub choice: VoteChoice,
+   pub label: String,
+   pub stored_votes: i64,
+   pub counted_votes: i64,
+}
+
+// How many characters of an id are shown in the lists, and the least accepted when looking one up
+pub const SHORT_CODE_LEN: usize = 8;
+pub const MIN_SHORT_CODE_LEN: usize = 4;
+
+pub fn short_code(id: &Uuid) -> String {
+   id.to_string()[..SHORT_CODE_LEN].to_string()
+}
+
+// Options used when the poll creator doesn't provide any
+pub const DEFAULT_OPTIONS: [&str; 2] = ["Yes", "No"];
+
+// How voters answer a poll: one option, every option they like ranked in order of preference,
+// every option they approve of, or a score for each option
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum PollKind {
+    #[default]
+    Single,
+    Ranked,
+    Approval,
+    Score,
+}
+
+impl PollKind {
+   // What a ballot of this kind of poll holds, as told to voters
+   pub fn ballot_description(&self) -> &'static str {
+      match self {
+            PollKind::Single => "a single option",
+            PollKind::Ranked => "the options ranked in order of preference",
+            PollKind::Approval => "every option you approve of",
+            PollKind::Score => "a score for each option",
+      }
+   }
+}
+
+impl FromStr for PollKind {
+   type Err = PollError;
+
+   fn from_str(input: &str) -> Result<Self, Self::Err> {
+      match input.trim().to_lowercase().as_str() {
+            "" | "single" => Ok(PollKind::Single),
+            "ranked" => Ok(PollKind::Ranked),
+            "approval" => Ok(PollKind::Approval),
+            "score" => Ok(PollKind::Score),
+            _ => Err(PollError::InvalidPollKind),
+      }
+   }
+}
+
+impl fmt::Display for PollKind {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollKind::Single => write!(f, "single"),
+            PollKind::Ranked => write!(f, "ranked"),
+            PollKind::Approval => write!(f, "approval"),
+            PollKind::Score => write!(f, "score"),
+        }
+    }
+}
+
+impl FromSql for PollKind {
+   fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value.as_str()?.parse().map_err(|_| FromSqlError::Other("Invalid poll kind".into()))
+    }
+}
+
+impl ToSql for PollKind {
+   fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl Serialize for PollKind {
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Where a poll is in its life, changed by hand. A draft takes no votes until it is opened, and an open poll
+// still closes by itself at the expiration date. Archived polls are closed for good
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum PollStatus {
+    Draft,
+    #[default]
+    Open,
+    Closed,
+    Archived,
+}
+
+impl FromStr for PollStatus {
+   type Err = PollError;
+
+   fn from_str(input: &str) -> Result<Self, Self::Err> {
+      match input.trim().to_lowercase().as_str() {
+            "draft" => Ok(PollStatus::Draft),
+            "open" => Ok(PollStatus::Open),
+            "closed" => Ok(PollStatus::Closed),
+            "archived" => Ok(PollStatus::Archived),
+            _ => Err(PollError::InvalidPollStatus),
+      }
+   }
+}
+
+impl fmt::Display for PollStatus {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollStatus::Draft => write!(f, "draft"),
+            PollStatus::Open => write!(f, "open"),
+            PollStatus::Closed => write!(f, "closed"),
+            PollStatus::Archived => write!(f, "archived"),
+        }
+    }
+}
+
+impl FromSql for PollStatus {
+   fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value.as_str()?.parse().map_err(|_| FromSqlError::Other("Invalid poll status".into()))
+    }
+}
+
+impl ToSql for PollStatus {
+   fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl Serialize for PollStatus {
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// What a poll needs to pass once it closes, and whether its votes are secret. The default is no quorum,
+// a simple majority and votes that show who cast them
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize)]
+pub struct PollRules {
+   pub quorum: i64, // Least turnout, abstentions included, for the result to count
+   pub threshold: Threshold,
+   pub anonymous: bool, // Votes are kept apart from their voters, who are only recorded as having voted. Weights don't count
 }
 
-#[derive(Debug)]
-pub struct ValidationError {
-   pub details: String,
+// Share of the votes cast, abstentions left out, the leading option needs to win
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Threshold {
+    #[default]
+    Majority, // More than half
+    AtLeast(i64, i64), // At least numerator/denominator, like 2/3
+}
+
+impl Threshold {
+   pub fn is_reached(&self, votes: i64, votes_cast: i64) -> bool {
+      match self {
+            Threshold::Majority => votes * 2 > votes_cast,
+            Threshold::AtLeast(numerator, denominator) => votes * denominator >= votes_cast * numerator,
+      }
+   }
+}
+
+impl FromStr for Threshold {
+   type Err = PollError;
+
+   // "majority" (or nothing), a fraction like "2/3" or a percentage like "60%"
+   fn from_str(input: &str) -> Result<Self, Self::Err> {
+      let input = input.trim().to_lowercase();
+
+      let threshold = if input.is_empty() || input == "majority" {
+            Some(Threshold::Majority)
+      } else if let Some(percentage) = input.strip_suffix('%') {
+            percentage.trim().parse::<i64>().ok().filter(|percentage| (1..=100).contains(percentage)).map(|percentage| Threshold::AtLeast(percentage, 100))
+      } else if let Some((numerator, denominator)) = input.split_once('/') {
+            match (numerator.trim().parse::<i64>(), denominator.trim().parse::<i64>()) {
+               (Ok(numerator), Ok(denominator)) if numerator > 0 && numerator <= denominator => Some(Threshold::AtLeast(numerator, denominator)),
+               _ => None,
+            }
+      } else {
+            None
+      };
+
+      threshold.ok_or(PollError::InvalidThreshold)
+   }
 }
 
-impl ValidationError {
-   pub fn new(msg: &str) -> ValidationError {
-        ValidationError {
-            details: msg.to_string(),
+impl fmt::Display for Threshold {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Threshold::Majority => write!(f, "majority"),
+            Threshold::AtLeast(percentage, 100) => write!(f, "{}%", percentage),
+            Threshold::AtLeast(numerator, denominator) => write!(f, "{}/{}", numerator, denominator),
         }
     }
 }
 
-impl fmt::Display for ValidationError {
-   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl FromSql for Threshold {
+   fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value.as_str()?.parse().map_err(|_| FromSqlError::Other("Invalid threshold".into()))
+    }
+}
+
+impl ToSql for Threshold {
+   fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl Serialize for Threshold {
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+   Passed,
+   Rejected,
+   NoQuorum,
+   Pending, // The poll is still open or didn't start
+}
+
+impl fmt::Display for Outcome {
+   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Passed => write!(f, "Passed"),
+            Outcome::Rejected => write!(f, "Rejected"),
+            Outcome::NoQuorum => write!(f, "No quorum"),
+            Outcome::Pending => write!(f, "Pending"),
+        }
     }
 }
 
-impl PartialEq for ValidationError {
-   fn eq(&self, other: &Self) -> bool {
-        self.details == other.details
+// A poll with its outcome, as exported by the results
+#[derive(Debug, Serialize)]
+pub struct PollResults<'a> {
+   #[serde(flatten)]
+   pub poll: &'a Poll,
+   pub outcome: Outcome,
+   pub winner: Option<&'a str>,
+}
+
+// How long a poll accepts votes, counted from its start date
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum PollDuration {
+    Hours(i64),
+    Days(i64),
+    Until(i64), // Fixed end date as a timestamp
+}
+
+impl PollDuration {
+   pub fn expiration_date(&self, start_date: i64) -> i64 {
+      match self {
+            PollDuration::Hours(hours) => start_date + hours*60*60,
+            PollDuration::Days(days) => start_date + days*24*60*60,
+            PollDuration::Until(end_date) => *end_date,
+      }
+   }
+}
+
+// Accepts dates as 2024-12-31, 2024-12-31 18:00, 2024-12-31T18:00 or RFC 3339, in local time
+pub fn parse_date(input: &str) -> Option<i64> {
+   let input = input.trim();
+
+   if let Ok(date) = DateTime::parse_from_rfc3339(input) {
+      return Some(date.timestamp());
    }
+
+   let naive_date_time = ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M:%S"]
+      .iter()
+      .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+      .or_else(|| NaiveDate::parse_from_str(input, "%Y-%m-%d").ok().and_then(|date| date.and_hms_opt(0, 0, 0)))?;
+
+   Local.from_local_datetime(&naive_date_time).earliest().map(|date| date.timestamp())
 }
 
-impl Error for ValidationError {}
+impl FromStr for PollDuration {
+   type Err = PollError;
+
+   // "36h" for hours, "3d" or just "3" for days, otherwise a date the poll ends at.
+   // A date without time keeps the poll open for the whole day.
+   fn from_str(input: &str) -> Result<Self, Self::Err> {
+      let input = input.trim();
+      let lowercase = input.to_lowercase();
+
+      let duration = if let Some(hours) = lowercase.strip_suffix('h') {
+            hours.trim().parse::<i64>().ok().filter(|hours| (1..=24*365).contains(hours)).map(PollDuration::Hours)
+      } else if let Some(days) = lowercase.strip_suffix('d') {
+            days.trim().parse::<i64>().ok().filter(|days| (1..=365).contains(days)).map(PollDuration::Days)
+      } else if let Ok(days) = input.parse::<i64>() {
+            Some(days).filter(|days| (1..=365).contains(days)).map(PollDuration::Days)
+      } else if NaiveDate::parse_from_str(input, "%Y-%m-%d").is_ok() {
+            parse_date(input).map(|date| PollDuration::Until(date + 24*60*60))
+      } else {
+            parse_date(input).map(PollDuration::Until)
+      };
+
+      duration.ok_or(PollError::InvalidDuration)
+   }
+}
 
 impl FromSql for PollDuration {
    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
-        match value.as_i64() {
-            Ok(7) => Ok(PollDuration::OneWeek),
-            Ok(30) => Ok(PollDuration::OneMonth),
-            Ok(_) | Err(_) => Err(FromSqlError::Other("Invalid poll duration".into())),
+        match value {
+            ValueRef::Integer(days) => Ok(PollDuration::Days(days)),
+            ValueRef::Text(text) => std::str::from_utf8(text)
+                .ok()
+                .and_then(|text| text.parse().ok())
+                .ok_or_else(|| FromSqlError::Other("Invalid poll duration".into())),
+            _ => Err(FromSqlError::InvalidType),
         }
     }
 }
 
 impl ToSql for PollDuration {
    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
-        Ok(ToSqlOutput::from(*self as i8))
+        Ok(ToSqlOutput::from(self.to_string()))
     }
 }
 
 impl fmt::Display for PollDuration {
    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            PollDuration::OneWeek => write!(f, "7"),
-            PollDuration::OneMonth => write!(f, "30"),
+            PollDuration::Hours(hours) => write!(f, "{}h", hours),
+            PollDuration::Days(days) => write!(f, "{}d", days),
+            PollDuration::Until(end_date) => write!(f, "{}", Local.timestamp_opt(*end_date, 0).unwrap().to_rfc3339()),
         }
     }
 }
 
+// Written the same way it is stored: 36h, 3d or the end date
+impl Serialize for PollDuration {
+   fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Dates are stored as timestamps but exported as RFC 3339
+pub fn serialize_date<S: Serializer>(date: &i64, serializer: S) -> Result<S::Ok, S::Error> {
+   serializer.collect_str(&Local.timestamp_opt(*date, 0).unwrap().to_rfc3339())
+}
+
+
+fn poll_from_row(row: &rusqlite::Row) -> Result<Poll> {
+   Ok(Poll {
+      id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+      question: row.get(1)?,
+      poll_duration: row.get(2)?,
+      create_date: row.get(3)?,
+      start_date: row.get(4)?,
+      expiration_date: row.get(5)?,
+      options: Vec::new(),
+      abstentions: row.get(6)?,
+      ballots: 0,
+      rules: PollRules {
+            quorum: row.get(7)?,
+            threshold: row.get(8)?,
+            anonymous: row.get(12)?,
+      },
+      kind: row.get(9)?,
+      status: row.get(10)?,
+      status_reason: row.get(11)?,
+      creator_id: row.get::<_, Option<String>>(13)?.map(|creator_id| Uuid::parse_str(&creator_id).unwrap()),
+      runoff: Vec::new(),
+   })
+}
+
+// Options, the ballots and the runoff rounds of ranked polls live outside the Poll row
+fn load_details(conn: &Connection, poll: &mut Poll) -> Result<()> {
+   poll.options = get_poll_options(conn, &poll.id)?;
+   poll.ballots = conn.query_row(
+      "SELECT COALESCE(SUM(voting_power), 0) FROM Vote WHERE poll_id = ?1 AND choice != 'abstain'",
+      [poll.id.to_string()],
+      |row| row.get(0),
+   )?;
+
+   if poll.kind == PollKind::Ranked {
+      poll.runoff = tally::instant_runoff(&poll.options, &vote::get_ballots(conn, &poll.id)?);
+   }
+
+   Ok(())
+}
 
+// Listed in the order they were created (rowid), which editing a poll doesn't change
 pub fn get_polls(conn: &Connection) -> Result<Vec<Poll>> {
-   let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes FROM Poll")?;
-   let poll_iter = stmt.query_map([], |row| {
-      Ok(Poll {
-            id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-            question: row.get(1)?,
-            poll_duration: row.get(2)?,
-            create_date: row.get(3)?,
-            expiration_date: row.get(4)?,
-            positive_votes: row.get(5)?,
-            negative_votes: row.get(6)?,
-      })
-   })?;
+   let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, start_date, expiration_date, abstentions, quorum, threshold, kind, status, status_reason, anonymous, creator_id FROM Poll ORDER BY rowid")?;
+   let poll_iter = stmt.query_map([], poll_from_row)?;
    let mut polls = Vec::new();
 
    for poll in poll_iter {
-      polls.push(poll?);
+      let mut poll = poll?;
+      load_details(conn, &mut poll)?;
+      polls.push(poll);
    }
 
    Ok(polls)
 }
 
-// Receive the question and the duration in days
-pub fn create_poll(conn: &Connection, question: String, input_days: String) -> Result<Poll, Box<dyn Error>>  {
-   let create_date: i64;
-   let expiration_date: i64;
-   
-   if question.trim().chars().count() > 0 {
-      if question.chars().count() <= 150{
-            
-      } else{
-            println!("\nQuestion is too long. Question only can have up to 150 chars.");
-            return Err(Box::new(ValidationError::new(
-               "Question is too long. Question only can have up to 150 chars.",
-            )));
+pub fn get_poll(conn: &Connection, id: &Uuid) -> Result<Option<Poll>> {
+   let poll = conn.query_row(
+      "SELECT id, question, poll_duration, create_date, start_date, expiration_date, abstentions, quorum, threshold, kind, status, status_reason, anonymous, creator_id FROM Poll WHERE id = ?1",
+      [id.to_string()],
+      poll_from_row,
+   ).optional()?;
+
+   match poll {
+      Some(mut poll) => {
+            load_details(conn, &mut poll)?;
+            Ok(Some(poll))
       }
-   } else{
-      println!("\nQuestion can't be empty");
-      return Err(Box::new(ValidationError::new(
-            "Question can't be empty.",
-      )));
-   }
-   
-   
-
-   let input_days_trimmed = input_days.trim();
-            
-   let poll_duration = match input_days_trimmed.parse::<i8>() {
-      Ok(7) =>  {
-            create_date = Local::now().timestamp();
-            expiration_date = create_date + 24*60*60*7;
-            Some(PollDuration::OneWeek)
-      },
-      Ok(30) =>  {
-            create_date = Local::now().timestamp();
-            expiration_date = create_date + 24*60*60*30;
-            Some(PollDuration::OneMonth)
+      None => Ok(None),
+   }
+}
+
+// Finds a poll by its full id or by the start of it, like the short code shown in the lists
+pub fn find_poll(conn: &Connection, reference: &str) -> Result<Option<Poll>, PollError> {
+   let reference = reference.trim().to_lowercase();
+
+   if let Ok(id) = Uuid::parse_str(&reference) {
+      return Ok(get_poll(conn, &id)?);
+   }
+
+   if reference.len() < MIN_SHORT_CODE_LEN || !reference.chars().all(|c| c.is_ascii_hexdigit() || c == '-') {
+      return Ok(None);
+   }
+
+   let mut stmt = conn.prepare("SELECT id FROM Poll WHERE id LIKE ?1 || '%' LIMIT 2")?;
+   let ids: Vec<String> = stmt.query_map([&reference], |row| row.get(0))?.collect::<Result<_>>()?;
+
+   match ids.as_slice() {
+      [] => Ok(None),
+      [id] => Ok(get_poll(conn, &Uuid::parse_str(id).unwrap())?),
+      _ => Err(PollError::AmbiguousShortCode(reference)),
+   }
+}
+
+pub fn get_poll_options(conn: &Connection, poll_id: &Uuid) -> Result<Vec<PollOption>> {
+   let mut stmt = conn.prepare("SELECT id, poll_id, label, votes FROM PollOption WHERE poll_id = ?1 ORDER BY position")?;
+   let option_iter = stmt.query_map([poll_id.to_string()], |row| {
+      Ok(PollOption {
+            id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+            poll_id: Uuid::parse_str(row.get::<_, String>(1)?.as_str()).unwrap(),
+            label: row.get(2)?,
+            votes: row.get(3)?,
+      })
+   })?;
+   let mut options = Vec::new();
+
+   for option in option_iter {
+      options.push(option?);
+   }
+
+   Ok(options)
+}
+
+impl Poll {
+   pub fn short_code(&self) -> String {
+      short_code(&self.id)
+   }
+
+   // Polls stop accepting votes once the expiration date is reached, or earlier when they are closed by hand
+   pub fn is_closed(&self) -> bool {
+      matches!(self.status, PollStatus::Closed | PollStatus::Archived) || Local::now().timestamp() >= self.expiration_date
+   }
+
+   // Drafts don't start until they are opened, even after the start date
+   pub fn has_started(&self) -> bool {
+      self.status != PollStatus::Draft && Local::now().timestamp() >= self.start_date
+   }
+
+   pub fn is_open(&self) -> bool {
+      self.has_started() && !self.is_closed()
+   }
+
+   // Votes given to the options, which decide the result. Approval and score ballots
+   // go to several options at once, so they are counted by themselves
+   pub fn votes_cast(&self) -> i64 {
+      match self.kind {
+            PollKind::Single | PollKind::Ranked => self.options.iter().map(|option| option.votes).sum(),
+            PollKind::Approval | PollKind::Score => self.ballots,
+      }
+   }
+
+   // The average score of an option on a score poll, every ballot scoring every option
+   pub fn average_score(&self, option: &PollOption) -> Option<f64> {
+      match self.kind {
+            PollKind::Score if self.ballots > 0 => Some(option.votes as f64 / self.ballots as f64),
+            _ => None,
+      }
+   }
+
+   // Everyone who took part, abstentions included
+   pub fn turnout(&self) -> i64 {
+      self.votes_cast() + self.abstentions
+   }
+
+   // The votes the result is decided on and their total: the option counters, or the last runoff round for
+   // ranked polls. An approval poll option is measured against every ballot, and the points of a score poll
+   // option against the most it could have had
+   fn final_tally(&self) -> (Vec<(&PollOption, i64)>, i64) {
+      let counters: Vec<(&PollOption, i64)> = self.options.iter().map(|option| (option, option.votes)).collect();
+
+      match (self.kind, self.runoff.last()) {
+            (PollKind::Single | PollKind::Approval, _) => (counters, self.votes_cast()),
+            (PollKind::Score, _) => (counters, self.votes_cast() * vote::MAX_SCORE),
+            (PollKind::Ranked, Some(round)) => (
+               round.tallies
+                  .iter()
+                  .filter_map(|tally| self.options.iter().find(|option| option.id == tally.option_id).map(|option| (option, tally.votes)))
+                  .collect(),
+               round.continuing(),
+            ),
+            (PollKind::Ranked, None) => (Vec::new(), 0),
+      }
+   }
+
+   // The option with the most votes in the final tally with its votes and the total,
+   // or None while there are no votes or the first place is tied
+   fn leader(&self) -> Option<(&PollOption, i64, i64)> {
+      let (final_tally, total) = self.final_tally();
+      let most_votes = final_tally.iter().map(|(_, votes)| *votes).max().filter(|votes| *votes > 0)?;
+      let mut leading = final_tally.iter().filter(|(_, votes)| *votes == most_votes);
+
+      match (leading.next(), leading.next()) {
+            (Some((option, votes)), None) => Some((option, *votes, total)),
+            _ => None,
+      }
+   }
+
+   pub fn leading_option(&self) -> Option<&PollOption> {
+      self.leader().map(|(option, _, _)| option)
+   }
+
+   // A poll passes when, after closing with enough turnout, its leading option reaches the threshold
+   pub fn outcome(&self) -> Outcome {
+      if !self.is_closed() {
+            return Outcome::Pending;
+      }
+
+      if self.turnout() < self.rules.quorum {
+            return Outcome::NoQuorum;
+      }
+
+      match self.leader() {
+            Some((_, votes, total)) if self.rules.threshold.is_reached(votes, total) => Outcome::Passed,
+            _ => Outcome::Rejected,
+      }
+   }
+
+   pub fn results(&self) -> PollResults<'_> {
+      let outcome = self.outcome();
+
+      PollResults {
+            poll: self,
+            outcome,
+            winner: match outcome {
+               Outcome::Passed => self.leading_option().map(|option| option.label.as_str()),
+               _ => None,
+            },
+      }
+   }
+
+   // Finds an option by its label (case insensitive) or by its 1-based position
+   pub fn find_option(&self, choice: &str) -> Option<&PollOption> {
+      let choice = choice.trim();
+
+      if let Some(option) = self.options.iter().find(|option| option.label.to_lowercase() == choice.to_lowercase()) {
+            return Some(option);
+      }
+
+      match choice.parse::<usize>() {
+            Ok(num) if num > 0 && num <= self.options.len() => Some(&self.options[num - 1]),
+            _ => None,
+      }
+   }
+
+   // Like find_option, but also accepts abstaining by its label or by the number after the last option.
+   // Ranked polls take the options in order of preference, separated by commas or >, like "2,1,3" or "Tea > Coffee".
+   // Approval polls take the approved options separated by commas, and score polls a score for each option in order, like "5,0,3"
+   pub fn find_choice(&self, choice: &str) -> Option<VoteChoice> {
+      if self.kind == PollKind::Score {
+            let scores: Option<Vec<i64>> = choice.split(',').map(|part| part.trim().parse().ok()).collect();
+
+            if let Some(scores) = scores.filter(|scores| scores.len() == self.options.len()) {
+               return Some(VoteChoice::Scored(self.options.iter().map(|option| option.id).zip(scores).collect()));
+            }
+      }
+
+      let separators: &[char] = match self.kind {
+            PollKind::Ranked => &[',', '>'],
+            PollKind::Approval => &[','],
+            PollKind::Single | PollKind::Score => &[],
+      };
+
+      if choice.contains(separators) {
+            let option_ids = choice
+               .split(separators)
+               .map(|part| self.find_option(part).map(|option| option.id))
+               .collect::<Option<Vec<Uuid>>>()?;
+
+            return Some(match self.kind {
+               PollKind::Approval => VoteChoice::Approved(option_ids),
+               _ => VoteChoice::Ranked(option_ids),
+            });
+      }
+
+      if let Some(option) = self.find_option(choice) {
+            return match self.kind {
+               PollKind::Single => Some(VoteChoice::Option(option.id)),
+               PollKind::Ranked => Some(VoteChoice::Ranked(vec![option.id])),
+               PollKind::Approval => Some(VoteChoice::Approved(vec![option.id])),
+               PollKind::Score => None,
+            };
+      }
+
+      let choice = choice.trim();
+
+      if choice.to_lowercase() == ABSTAIN_LABEL.to_lowercase() || choice == (self.options.len() + 1).to_string() {
+            Some(VoteChoice::Abstain)
+      } else {
+            None
+      }
+   }
+}
+
+fn validate_options(options: &[String]) -> Result<Vec<String>, PollError> {
+   let options: Vec<String> = options.iter().map(|option| option.trim().to_string()).collect();
+
+   if options.len() < 2 {
+      return Err(PollError::TooFewOptions { min: 2 });
+   }
+
+   if options.len() > 10 {
+      return Err(PollError::TooManyOptions { max: 10 });
+   }
+
+   for (i, option) in options.iter().enumerate() {
+      if option.is_empty() {
+            return Err(PollError::OptionEmpty);
+      }
+
+      if option.chars().count() > 50 {
+            return Err(PollError::OptionTooLong { max: 50 });
+      }
+
+      if options[..i].iter().any(|other| other.to_lowercase() == option.to_lowercase()) {
+            return Err(PollError::DuplicateOptions);
       }
-      Ok(_) | Err(_) => {
-            println!("\nInvalid input for Poll Duration. Please enter 7 or 30 Days.");
-            return Err(Box::new(ValidationError::new(
-               "Invalid input for Poll Duration. Must be 7 or 30.",
-            )));
+   }
+
+   Ok(options)
+}
+
+// Receive the question, the duration ("36h", "3d" or an end date), the options voters can choose from,
+// when voting starts (empty to start right away), the rules it needs to pass and how voters answer it
+#[allow(clippy::too_many_arguments)]
+pub fn create_poll(conn: &Connection, question: String, input_duration: String, options: Vec<String>, input_start: String, rules: PollRules, kind: PollKind, creator: Option<&User>) -> Result<Poll, PollError>  {
+   if question.trim().is_empty() {
+      return Err(PollError::QuestionEmpty);
+   }
+
+   if question.chars().count() > 150 {
+      return Err(PollError::QuestionTooLong { max: 150 });
+   }
+
+   let create_date = Local::now().timestamp();
+
+   let start_date = if input_start.trim().is_empty() {
+      create_date
+   } else {
+      match parse_date(&input_start) {
+            Some(start_date) if start_date >= create_date => start_date,
+            Some(_) => {
+               return Err(PollError::StartDateInPast);
+            }
+            None => {
+               return Err(PollError::InvalidStartDate);
+            }
       }
    };
 
-   
+   let poll_duration = input_duration.parse::<PollDuration>()?;
+
+   let expiration_date = poll_duration.expiration_date(start_date);
+
+   if expiration_date <= start_date {
+      return Err(PollError::EndsBeforeStart);
+   }
+
+   let options = validate_options(&options)?;
+
+   if rules.quorum < 0 {
+      return Err(PollError::InvalidQuorum);
+   }
+
+   let id = Uuid::new_v4();
 
    let poll = Poll {
-      id: Uuid::new_v4(),
+      id,
       question: question.trim().to_string(),
-      poll_duration: poll_duration.expect("Poll duration can't be empty"),
+      poll_duration,
       create_date,
+      start_date,
       expiration_date,
-      positive_votes: 0,
-      negative_votes: 0,
+      options: options
+            .into_iter()
+            .map(|label| PollOption {
+               id: Uuid::new_v4(),
+               poll_id: id,
+               label,
+               votes: 0,
+            })
+            .collect(),
+      abstentions: 0,
+      ballots: 0,
+      rules,
+      kind,
+      status: PollStatus::Open,
+      status_reason: String::new(),
+      creator_id: creator.map(|creator| creator.id),
+      runoff: Vec::new(),
    };
 
-   let _ = conn.execute(
-      "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-      [
+   // The poll and its options are saved together or not at all
+   let tx = conn.unchecked_transaction()?;
+
+   tx.execute(
+      "INSERT INTO Poll (id, question, poll_duration, create_date, start_date, expiration_date, quorum, threshold, kind, anonymous, creator_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+      rusqlite::params![
             &poll.id.to_string(),
             &poll.question,
             &poll.poll_duration.to_string(),
             &poll.create_date.to_string(),
+            &poll.start_date.to_string(),
             &poll.expiration_date.to_string(),
-            &poll.positive_votes.to_string(),
-            &poll.negative_votes.to_string(),
+            &poll.rules.quorum.to_string(),
+            &poll.rules.threshold.to_string(),
+            &poll.kind.to_string(),
+            &(poll.rules.anonymous as i64).to_string(),
+            poll.creator_id.map(|creator_id| creator_id.to_string()),
       ],
-   );
+   )?;
 
-   println!("\nPoll Created!");
+   for (position, option) in poll.options.iter().enumerate() {
+      tx.execute(
+            "INSERT INTO PollOption (id, poll_id, label, position, votes) VALUES (?1, ?2, ?3, ?4, ?5)",
+            (option.id.to_string(), option.poll_id.to_string(), &option.label, position as i64, option.votes),
+      )?;
+   }
+
+   tx.commit()?;
 
    Ok(poll)
 }
 
-pub fn edit_poll(conn: &Connection, choice1: String, choice2:String, new_question: String, input_days: String) -> Result<Poll, Box<dyn Error>>  {
-   let poll_duration: Option<PollDuration>;
-   let create_date;
-   let expiration_date;
-   
-   let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes FROM Poll")?;
-   let poll_iter = stmt.query_map([], |row| {
-      Ok(Poll {
-            id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-            question: row.get(1)?,
-            poll_duration: row.get(2)?,
-            create_date: row.get(3)?,
-            expiration_date: row.get(4)?,
-            positive_votes: row.get(5)?,
-            negative_votes: row.get(6)?,
-      })
-   })?;
-   let mut polls = Vec::new();
+// Saves the new question and duration, keeping the old ones and the editor as a revision
+pub fn edit_poll(conn: &Connection, poll_id: &Uuid, editor: &User, choice2:String, new_question: String, input_duration: String) -> Result<Poll, PollError>  {
+   let selected_poll = match get_poll(conn, poll_id)? {
+      Some(poll) => poll,
+      None => {
+            return Err(PollError::PollNotFound(poll_id.to_string()));
+      }
+   };
 
-   for poll in poll_iter {
-      polls.push(poll?);
+   if new_question.trim().is_empty() {
+      return Err(PollError::QuestionEmpty);
    }
 
-   if polls.is_empty() {
-      println!("\nThere are no polls to Edit.");
-      return Err(Box::new(ValidationError::new(
-            "There are no polls to Edit.",
-      )));
+   if new_question.chars().count() > 150 {
+      return Err(PollError::QuestionTooLong { max: 150 });
    }
 
-   let _choice1: usize = match choice1.trim().parse() {
-      Ok(num) if num > 0 && num <= polls.len() => num,
-      _ => {
-            println!("\nInvalid input for selecting Poll. Please enter a valid number.");
-            return Err(Box::new(ValidationError::new(
-               "Invalid input for selecting Poll. Please enter a valid number.",
-            )));
-      }
-   };
+   let (poll_duration, expiration_date) = match choice2.trim() {
+      "n" => (selected_poll.poll_duration, selected_poll.expiration_date),
+      "y" => {
+            let poll_duration = input_duration.parse::<PollDuration>()?;
 
-   let choice1: usize = choice1.trim().parse().unwrap();
+            // A poll that is already running restarts its duration from now, but keeps its creation date
+            let now = Local::now().timestamp();
+            let expiration_date = poll_duration.expiration_date(selected_poll.start_date.max(now));
 
-   let selected_poll = &polls[choice1 - 1];
+            if expiration_date <= now {
+               return Err(PollError::EndsInPast);
+            }
 
-   if new_question.trim().chars().count() > 0 {
-      if new_question.chars().count() <= 150{
-      } else{
-            println!("\nQuestion is too long. Question only can have up to 150 chars.");
-            return Err(Box::new(ValidationError::new(
-               "Question is too long. Question only can have up to 150 chars.",
-            )));
-      }
-      } else{
-            println!("\nQuestion can't be empty.");
-            return Err(Box::new(ValidationError::new(
-               "Question can't be empty.",
-            )));
+            (poll_duration, expiration_date)
       }
-   
-
-      if choice2.trim() == "n" {
-            poll_duration = Some(selected_poll.poll_duration);
-            create_date = selected_poll.create_date;
-            expiration_date = selected_poll.expiration_date;
-      } else if choice2.trim() == "y"{ 
-
-            println!("{}", input_days);
-
-            let input_days_trimmed = input_days.trim();
-            
-            poll_duration = match input_days_trimmed.parse::<i8>() {
-               Ok(7) => {
-                  create_date = Local::now().timestamp();
-                  expiration_date = create_date + 24*60*60*7;
-                  Some(PollDuration::OneWeek)
-               },
-               Ok(30) => {
-                  create_date = Local::now().timestamp();
-                  expiration_date = create_date + 24*60*60*30;
-                  Some(PollDuration::OneMonth) 
-               }
-               Ok(_) | Err(_) => {
-                  println!("\nInvalid input. Please enter 7 or 30 Days.");
-                  return Err(Box::new(ValidationError::new(
-                        "Invalid input for Poll Duration. Please enter 7 or 30 Days.",
-                  )));
-               }
-            };   
-      }else{
-            println!("\nInvalid input for choice 2. Please enter 'y' or 'n'.");
-            return Err(Box::new(ValidationError::new(
-               "Invalid input for choice 2. Please enter 'y' or 'n'.",
-            )));
-      }
-
+      _ => return Err(PollError::InvalidConfirmation),
+   };
 
    let poll = Poll {
       id: selected_poll.id,
       question: new_question.trim().to_string(),
-      poll_duration: poll_duration.expect("Poll duration can't be empty"),
-      create_date,
+      poll_duration,
+      create_date: selected_poll.create_date,
+      start_date: selected_poll.start_date,
       expiration_date,
-      positive_votes: selected_poll.positive_votes,
-      negative_votes: selected_poll.negative_votes,
+      options: selected_poll.options.clone(),
+      abstentions: selected_poll.abstentions,
+      ballots: selected_poll.ballots,
+      rules: selected_poll.rules,
+      kind: selected_poll.kind,
+      status: selected_poll.status,
+      status_reason: selected_poll.status_reason.clone(),
+      creator_id: selected_poll.creator_id,
+      runoff: selected_poll.runoff.clone(),
    };
 
+   // The revision and the edit are saved together or not at all
+   let tx = conn.unchecked_transaction()?;
 
-   conn.execute(
-      "UPDATE Poll SET question = ?1, poll_duration = ?2, create_date = ?3, expiration_date = ?4  WHERE id = ?5",
+   tx.execute(
+      "INSERT INTO PollRevision (id, poll_id, question, poll_duration, expiration_date, edit_date, editor_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+      (
+            Uuid::new_v4().to_string(),
+            selected_poll.id.to_string(),
+            &selected_poll.question,
+            &selected_poll.poll_duration,
+            selected_poll.expiration_date,
+            Local::now().timestamp(),
+            editor.id.to_string(),
+      ),
+   )?;
+
+   tx.execute(
+      "UPDATE Poll SET question = ?1, poll_duration = ?2, expiration_date = ?3 WHERE id = ?4",
       [
             &poll.question,
             &poll.poll_duration.to_string(),
-            &poll.create_date.to_string(),
             &poll.expiration_date.to_string(),
             &poll.id.to_string()
       ],
    )?;
 
-   println!("\nPoll {} edited Successfully", choice1);
+   tx.commit()?;
 
    Ok(poll)
 }
 
-pub fn delete_poll(conn: &Connection, choice: String, confirmation: String) -> Result<Poll, Box<dyn Error>>{
+// Moves a poll to another status by hand, keeping the reason given. Drafts can be opened or closed, open polls
+// closed, or turned back into drafts while nobody has voted, and closed polls reopened while their expiration
+// date is ahead or archived. Nothing else about the poll, its creation date included, changes
+pub fn set_poll_status(conn: &Connection, poll_id: &Uuid, status: PollStatus, reason: String) -> Result<Poll, PollError> {
+   let mut poll = match get_poll(conn, poll_id)? {
+      Some(poll) => poll,
+      None => {
+            return Err(PollError::PollNotFound(poll_id.to_string()));
+      }
+   };
+
+   if reason.trim().chars().count() > 100 {
+      return Err(PollError::ReasonTooLong { max: 100 });
+   }
+
+   let expired = Local::now().timestamp() >= poll.expiration_date;
+
+   match (poll.status, status) {
+      (PollStatus::Open, PollStatus::Draft) if poll.turnout() > 0 => return Err(PollError::PollHasVotes),
+      (PollStatus::Draft | PollStatus::Closed, PollStatus::Open) if expired => return Err(PollError::EndsInPast),
+      (PollStatus::Open, PollStatus::Draft)
+      | (PollStatus::Draft | PollStatus::Closed, PollStatus::Open)
+      | (PollStatus::Draft | PollStatus::Open, PollStatus::Closed) => {}
+      (PollStatus::Closed, PollStatus::Archived) => {}
+      // An open poll past its expiration date is already closed, so it can go straight to the archive
+      (PollStatus::Open, PollStatus::Archived) if expired => {}
+      (from, to) => return Err(PollError::InvalidStatusChange { from, to }),
+   }
+
+   poll.status = status;
+   poll.status_reason = reason.trim().to_string();
+
+   conn.execute(
+      "UPDATE Poll SET status = ?1, status_reason = ?2 WHERE id = ?3",
+      (poll.status, &poll.status_reason, poll.id.to_string()),
+   )?;
+
+   Ok(poll)
+}
 
-   let mut stmt = conn.prepare("SELECT id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes FROM Poll")?;
-   let poll_iter = stmt.query_map([], |row| {
-      Ok(Poll {
+// Every edit of a poll, oldest first
+pub fn get_poll_revisions(conn: &Connection, poll_id: &Uuid) -> Result<Vec<PollRevision>> {
+   let mut stmt = conn.prepare(
+      "SELECT PollRevision.id, poll_id, question, poll_duration, expiration_date, edit_date, COALESCE(name, 'Unknown')
+      FROM PollRevision LEFT JOIN User ON PollRevision.editor_id = User.id WHERE poll_id = ?1 ORDER BY PollRevision.rowid"
+   )?;
+   let revision_iter = stmt.query_map([poll_id.to_string()], |row| {
+      Ok(PollRevision {
             id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-            question: row.get(1)?,
-            poll_duration: row.get(2)?,
-            create_date: row.get(3)?,
+            poll_id: Uuid::parse_str(row.get::<_, String>(1)?.as_str()).unwrap(),
+            question: row.get(2)?,
+            poll_duration: row.get(3)?,
             expiration_date: row.get(4)?,
-            positive_votes: row.get(5)?,
-            negative_votes: row.get(6)?,
+            edit_date: row.get(5)?,
+            editor_name: row.get(6)?,
       })
    })?;
-   let mut polls = Vec::new();
 
-   for poll in poll_iter {
-      polls.push(poll?);
-   }
-   
-   if polls.is_empty() {
-      println!("\nThere are no polls to Delete.");
-      return Err(Box::new(ValidationError::new(
-            "There are no polls to Delete.",
-      )));
-   }
-   
-   let _choice: usize = match choice.trim().parse() {
-      Ok(num) if num > 0 && num <= polls.len() => num,
-      _ => {
-            println!("\nInvalid input for selecting Poll. Please enter a valid number.");
-            return Err(Box::new(ValidationError::new(
-               "Invalid input for selecting Poll. Please enter a valid number.",
-            )));
+   revision_iter.collect()
+}
+
+pub fn delete_poll(conn: &Connection, poll_id: &Uuid, confirmation: String) -> Result<Poll, PollError>{
+   if confirmation.trim() == "y" {
+      delete_poll_by_id(conn, poll_id)
+   } else{
+      Err(PollError::NotConfirmed)
+   }
+}
+
+// Removes the poll with its options, votes and revisions
+pub fn delete_poll_by_id(conn: &Connection, id: &Uuid) -> Result<Poll, PollError> {
+   let tx = conn.unchecked_transaction()?;
+
+   let poll = match get_poll(&tx, id)? {
+      Some(poll) => poll,
+      None => {
+            return Err(PollError::PollNotFound(id.to_string()));
       }
    };
 
-   let choice: usize = choice.trim().parse().unwrap();
+   // The log keeps the votes, so it records them going with the poll
+   vote::retract_poll_votes(&tx, id)?;
 
-   if confirmation.trim() == "y" {
-      //Check if some poll.id is the same as poll_id. If it is equal it returns the index position then remove the poll from the polls list.
-      if let Some(_index) = polls.iter().position(|poll| poll.id == polls[choice - 1].id) {
-            conn.execute(
-               "DELETE FROM Vote WHERE poll_id = ?1",
-               [polls[choice - 1].id.to_string().as_str()],
-            )?;
-
-            conn.execute(
-               "DELETE FROM Poll WHERE id = ?1",
-               [polls[choice - 1].id.to_string().as_str()],
-            )?;
-            println!("\nPoll Removed Successfuly!");
-            Ok((polls[choice - 1]).clone())
-      } else {
-            panic!("Error When Deleting the Poll. Can't Found Same ID");
+   tx.execute(
+      "DELETE FROM BallotEntry WHERE vote_id IN (SELECT id FROM Vote WHERE poll_id = ?1)",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.execute(
+      "DELETE FROM Vote WHERE poll_id = ?1",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.execute(
+      "DELETE FROM Participation WHERE poll_id = ?1",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.execute(
+      "DELETE FROM PollRevision WHERE poll_id = ?1",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.execute(
+      "DELETE FROM PollOption WHERE poll_id = ?1",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.execute(
+      "DELETE FROM Poll WHERE id = ?1",
+      [id.to_string().as_str()],
+   )?;
+
+   tx.commit()?;
+
+   Ok(poll)
+}
+
+// Recounts every option and the abstentions of every poll from the Vote table, summing the voting power of each vote,
+// times the score on score polls, and returns the ones whose counter drifted. Approval and score ballots are recounted
+// from BallotEntry, since they go to several options. With repair the counters are rewritten with the recounted values.
+pub fn verify_tallies(conn: &Connection, repair: bool) -> Result<Vec<TallyMismatch>> {
+   let tx = conn.unchecked_transaction()?;
+
+   let mut mismatches = Vec::new();
+
+   {
+      let mut stmt = tx.prepare(
+            "SELECT PollOption.poll_id, question, PollOption.id, label, votes,
+                  CASE kind
+                        WHEN 'approval' THEN (SELECT COALESCE(SUM(voting_power), 0) FROM BallotEntry JOIN Vote ON BallotEntry.vote_id = Vote.id
+                              WHERE BallotEntry.option_id = PollOption.id)
+                        WHEN 'score' THEN (SELECT COALESCE(SUM(voting_power * score), 0) FROM BallotEntry JOIN Vote ON BallotEntry.vote_id = Vote.id
+                              WHERE BallotEntry.option_id = PollOption.id)
+                        ELSE (SELECT COALESCE(SUM(voting_power), 0) FROM Vote WHERE Vote.choice = PollOption.id)
+                  END,
+                  Poll.rowid AS poll_order, position
+            FROM PollOption JOIN Poll ON PollOption.poll_id = Poll.id
+            UNION ALL
+            SELECT id, question, 'abstain', 'Abstain', abstentions,
+                  (SELECT COALESCE(SUM(voting_power), 0) FROM Vote WHERE Vote.poll_id = Poll.id AND Vote.choice = 'abstain'),
+                  rowid, (SELECT COUNT(*) FROM PollOption WHERE PollOption.poll_id = Poll.id)
+            FROM Poll
+            ORDER BY poll_order, position"
+      )?;
+      let option_iter = stmt.query_map([], |row| {
+            Ok(TallyMismatch {
+               poll_id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+               question: row.get(1)?,
+               choice: row.get(2)?,
+               label: row.get(3)?,
+               stored_votes: row.get(4)?,
+               counted_votes: row.get(5)?,
+            })
+      })?;
+
+      for option in option_iter {
+            let option = option?;
+
+            if option.stored_votes != option.counted_votes {
+               mismatches.push(option);
+            }
       }
-   } else{
-      println!("\nCanceling operation");
-      Err(Box::new(ValidationError::new(
-            "Canceling operation. Not a Valid Confirmation.",
-      )))
    }
+
+   if repair {
+      for mismatch in &mismatches {
+            match mismatch.choice {
+               VoteChoice::Option(option_id) => tx.execute(
+                  "UPDATE PollOption SET votes = ?1 WHERE id = ?2",
+                  (mismatch.counted_votes, option_id.to_string()),
+               )?,
+               _ => tx.execute(
+                  "UPDATE Poll SET abstentions = ?1 WHERE id = ?2",
+                  (mismatch.counted_votes, mismatch.poll_id.to_string()),
+               )?,
+            };
+      }
+   }
+
+   tx.commit()?;
+
+   Ok(mismatches)
 }
@@ -0,0 +1,333 @@
+use rusqlite::Connection;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiny_http::{Header, Server};
+use uuid::Uuid;
+use std::error::Error;
+
+use crate::error::PollError;
+use crate::permission::{self, Action};
+use crate::poll;
+use crate::user::{self, User};
+use crate::vote;
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Value,
+}
+
+impl Response {
+    fn new(status: u16, body: Value) -> Response {
+        Response { status, body }
+    }
+
+    fn error(status: u16, error: &str, message: &str) -> Response {
+        Response::new(status, json!({ "error": error, "message": message }))
+    }
+
+    fn not_found(message: &str) -> Response {
+        Response::error(404, "not_found", message)
+    }
+}
+
+#[derive(Deserialize)]
+struct NewPoll {
+    question: String,
+    duration: String,
+    #[serde(default)]
+    options: Vec<String>,
+    #[serde(default)]
+    start: String,
+    #[serde(default)]
+    quorum: i64,
+    #[serde(default)]
+    threshold: String,
+    #[serde(default)]
+    kind: String,
+    #[serde(default)]
+    anonymous: bool,
+}
+
+#[derive(Deserialize)]
+struct Credentials {
+    name: String,
+    password: String,
+}
+
+// A voter who was invited signs up with the invite instead of a name
+#[derive(Deserialize)]
+struct NewUser {
+    #[serde(default)]
+    name: String,
+    password: String,
+    invite: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct NewVote {
+    choice: String,
+    #[serde(default)]
+    comment: String,
+}
+
+#[derive(Deserialize)]
+struct VoteChanges {
+    choice: String,
+    #[serde(default)]
+    comment: String,
+}
+
+// Problems with the request get a 4xx, failures of the database a 500
+fn error_response(err: PollError) -> Response {
+    let mut response = match &err {
+        PollError::PollNotFound(_) | PollError::VoteNotFound(_) | PollError::ReceiptNotFound => Response::not_found(&err.to_string()),
+        PollError::PollClosed { .. } => Response::error(409, "poll_closed", &err.to_string()),
+        PollError::PollNotStarted { .. } => Response::error(409, "poll_not_started", &err.to_string()),
+        PollError::PollNotOpen { .. } => Response::error(409, "poll_not_open", &err.to_string()),
+        PollError::AlreadyVoted { .. } => Response::error(409, "already_voted", &err.to_string()),
+        PollError::AnonymousVote(_) => Response::error(409, "anonymous_vote", &err.to_string()),
+        PollError::InvalidLogin => Response::error(401, "invalid_login", &err.to_string()),
+        PollError::LoginRequired => Response::error(401, "login_required", &err.to_string()),
+        PollError::PermissionDenied(_) => Response::error(403, "permission_denied", &err.to_string()),
+        PollError::NoAdmin => Response::error(403, "no_admin", &err.to_string()),
+        PollError::Database(_) => Response::error(500, "internal", &err.to_string()),
+        _ => Response::error(400, "validation", &err.to_string()),
+    };
+
+    match err {
+        PollError::PollClosed { expiration_date, .. } => response.body["expiration_date"] = json!(expiration_date),
+        PollError::PollNotStarted { start_date, .. } => response.body["start_date"] = json!(start_date),
+        PollError::PollNotOpen { status, .. } => response.body["status"] = json!(status),
+        _ => {}
+    }
+
+    response
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a str) -> Result<T, Response> {
+    serde_json::from_str(body).map_err(|err| Response::error(400, "invalid_body", &err.to_string()))
+}
+
+fn find_poll(conn: &Connection, id: &str) -> Result<poll::Poll, Response> {
+    let poll = Uuid::parse_str(id).ok().map(|id| poll::get_poll(conn, &id)).transpose();
+
+    match poll {
+        Ok(Some(Some(poll))) => Ok(poll),
+        Ok(_) => Err(error_response(PollError::PollNotFound(id.to_string()))),
+        Err(err) => Err(error_response(err.into())),
+    }
+}
+
+fn find_vote(conn: &Connection, id: &str) -> Result<vote::Vote, Response> {
+    let vote = Uuid::parse_str(id).ok().map(|id| vote::get_vote(conn, &id)).transpose();
+
+    match vote {
+        Ok(Some(Some(vote))) => Ok(vote),
+        Ok(_) => Err(Response::not_found("Vote not found.")),
+        Err(err) => Err(error_response(err.into())),
+    }
+}
+
+fn to_json<T: serde::Serialize>(status: u16, value: &T) -> Response {
+    match serde_json::to_value(value) {
+        Ok(body) => Response::new(status, body),
+        Err(err) => Response::error(500, "internal", &err.to_string()),
+    }
+}
+
+// Everything but reading needs a token from POST /sessions
+fn require_user(user: Option<&User>) -> Result<&User, Response> {
+    user.ok_or_else(|| error_response(PollError::LoginRequired))
+}
+
+fn start_session(conn: &Connection, body: &str) -> Result<Response, Response> {
+    let credentials: Credentials = parse_body(body)?;
+    let user = user::login(conn, &credentials.name, &credentials.password).map_err(error_response)?;
+    let token = user::create_session(conn, &user).map_err(|err| error_response(err.into()))?;
+
+    Ok(Response::new(201, json!({ "token": token, "user": user })))
+}
+
+// Votes are cast as the voter of the logged in user
+fn cast_vote(conn: &Connection, user: &User, poll_id: &str, body: &str) -> Result<Response, Response> {
+    permission::check(user, Action::CastVote).map_err(error_response)?;
+
+    let new_vote: NewVote = parse_body(body)?;
+    let poll = find_poll(conn, poll_id)?;
+    let voter = user::user_voter(conn, user).map_err(|err| error_response(err.into()))?;
+
+    let choice = match poll.find_choice(&new_vote.choice) {
+        Some(choice) => choice,
+        None => return Err(error_response(PollError::InvalidChoice(new_vote.choice))),
+    };
+
+    let vote = vote::create_vote(conn, poll, &voter, choice, new_vote.comment).map_err(error_response)?;
+
+    Ok(to_json(201, &vote))
+}
+
+fn change_vote(conn: &Connection, user: &User, vote_id: &str, body: &str) -> Result<Response, Response> {
+    let changes: VoteChanges = parse_body(body)?;
+    let current_vote = find_vote(conn, vote_id)?;
+
+    permission::check_vote(user, &current_vote).map_err(error_response)?;
+    let poll = find_poll(conn, &current_vote.poll_id.to_string())?;
+
+    let choice = match poll.find_choice(&changes.choice) {
+        Some(choice) => choice,
+        None => return Err(error_response(PollError::InvalidChoice(changes.choice))),
+    };
+
+    let vote = vote::edit_vote(conn, &current_vote, choice, changes.comment).map_err(error_response)?;
+
+    Ok(to_json(200, &vote))
+}
+
+fn find_receipt(conn: &Connection, receipt: &str) -> Result<vote::Vote, Response> {
+    match vote::get_vote_by_receipt(conn, receipt) {
+        Ok(Some(vote)) => Ok(vote),
+        Ok(None) => Err(error_response(PollError::ReceiptNotFound)),
+        Err(err) => Err(error_response(err.into())),
+    }
+}
+
+// Same as change_vote, for whoever holds the receipt. It is the only way to change a vote on an anonymous poll
+fn change_vote_by_receipt(conn: &Connection, user: &User, receipt: &str, body: &str) -> Result<Response, Response> {
+    let changes: VoteChanges = parse_body(body)?;
+    let current_vote = find_receipt(conn, receipt)?;
+
+    permission::check_vote(user, &current_vote).map_err(error_response)?;
+    let poll = find_poll(conn, &current_vote.poll_id.to_string())?;
+
+    let choice = match poll.find_choice(&changes.choice) {
+        Some(choice) => choice,
+        None => return Err(error_response(PollError::InvalidChoice(changes.choice))),
+    };
+
+    let vote = vote::edit_vote_by_receipt(conn, receipt, choice, changes.comment).map_err(error_response)?;
+
+    Ok(to_json(200, &vote))
+}
+
+// The first account is the admin, so it is made from the command line next to the database. Until it is,
+// nobody signs up here, or whoever reached the server first would be the admin
+fn sign_up(conn: &Connection, new_user: NewUser) -> Result<Response, Response> {
+    let user = match new_user.invite {
+        Some(invite) => user::join(conn, &invite, &new_user.password),
+        None => {
+            if user::get_users(conn).map_err(|err| error_response(err.into()))?.is_empty() {
+                return Err(error_response(PollError::NoAdmin));
+            }
+
+            user::create_user(conn, new_user.name, &new_user.password)
+        }
+    };
+
+    user.map(|user| to_json(201, &user)).map_err(error_response)
+}
+
+// Routes a request to the poll and vote functions. Kept apart from the socket so it can be tested directly.
+// The token is the one from the Authorization header, if it had one
+pub fn handle(conn: &Connection, method: &str, url: &str, token: Option<&str>, body: &str) -> Response {
+    let path = url.split('?').next().unwrap_or_default();
+    let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+    // A token that is unknown or expired is the same as none
+    let user = match token.map(|token| user::session_user(conn, token)).transpose() {
+        Ok(user) => user.flatten(),
+        Err(err) => return error_response(err.into()),
+    };
+
+    let response = match (method, segments.as_slice()) {
+        ("POST", ["users"]) => parse_body::<NewUser>(body).and_then(|new_user| sign_up(conn, new_user)),
+        ("POST", ["sessions"]) => start_session(conn, body),
+        ("DELETE", ["sessions"]) => require_user(user.as_ref()).and_then(|_| {
+            user::end_session(conn, token.unwrap_or_default())
+                .map(|_| Response::new(204, Value::Null))
+                .map_err(|err| error_response(err.into()))
+        }),
+        ("GET", ["polls"]) => poll::get_polls(conn)
+            .map(|polls| to_json(200, &polls))
+            .map_err(|err| error_response(err.into())),
+        ("POST", ["polls"]) => require_user(user.as_ref()).and_then(|creator| {
+            permission::check(creator, Action::CreatePoll).map_err(error_response)?;
+
+            let new_poll: NewPoll = parse_body(body)?;
+
+            let options = if new_poll.options.is_empty() {
+                poll::DEFAULT_OPTIONS.iter().map(|option| option.to_string()).collect()
+            } else {
+                new_poll.options
+            };
+
+            let threshold = new_poll.threshold.parse().map_err(error_response)?;
+            let rules = poll::PollRules { quorum: new_poll.quorum, threshold, anonymous: new_poll.anonymous };
+            let kind = new_poll.kind.parse().map_err(error_response)?;
+
+            poll::create_poll(conn, new_poll.question, new_poll.duration, options, new_poll.start, rules, kind, Some(creator))
+                .map(|poll| to_json(201, &poll))
+                .map_err(error_response)
+        }),
+        ("GET", ["polls", id, "results"]) => find_poll(conn, id).map(|poll| to_json(200, &poll.results())),
+        ("DELETE", ["polls", id]) => require_user(user.as_ref()).and_then(|user| {
+            let poll = find_poll(conn, id)?;
+
+            permission::check_poll(user, &poll).map_err(error_response)?;
+
+            poll::delete_poll_by_id(conn, &poll.id)
+                .map(|_| Response::new(204, Value::Null))
+                .map_err(error_response)
+        }),
+        ("POST", ["polls", id, "votes"]) => require_user(user.as_ref()).and_then(|user| cast_vote(conn, user, id, body)),
+        ("PATCH", ["votes", id]) => require_user(user.as_ref()).and_then(|user| change_vote(conn, user, id, body)),
+        ("GET", ["receipts", receipt]) => find_receipt(conn, receipt).map(|vote| to_json(200, &vote)),
+        ("PATCH", ["receipts", receipt]) => require_user(user.as_ref()).and_then(|user| change_vote_by_receipt(conn, user, receipt, body)),
+        ("DELETE", ["receipts", receipt]) => require_user(user.as_ref()).and_then(|user| {
+            permission::check_vote(user, &find_receipt(conn, receipt)?).map_err(error_response)?;
+
+            vote::delete_vote_by_receipt(conn, receipt)
+                .map(|_| Response::new(204, Value::Null))
+                .map_err(error_response)
+        }),
+        (_, ["users"]) | (_, ["sessions"]) | (_, ["polls"]) | (_, ["polls", _]) | (_, ["polls", _, "results"]) | (_, ["polls", _, "votes"]) | (_, ["votes", _]) | (_, ["receipts", _]) => {
+            Err(Response::error(405, "method_not_allowed", "Method not allowed."))
+        }
+        _ => Err(Response::not_found("Not found.")),
+    };
+
+    response.unwrap_or_else(|response| response)
+}
+
+pub fn serve(conn: &Connection, addr: &str) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(addr).map_err(|err| err.to_string())?;
+    let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+
+    for mut request in server.incoming_requests() {
+        let mut body = String::new();
+
+        let response = match request.as_reader().read_to_string(&mut body) {
+            Ok(_) => {
+                let token = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Authorization"))
+                    .and_then(|header| header.value.as_str().strip_prefix("Bearer "))
+                    .map(|token| token.to_string());
+
+                handle(conn, request.method().as_str(), request.url(), token.as_deref(), &body)
+            }
+            Err(_) => Response::error(400, "invalid_body", "The request body must be UTF-8 JSON."),
+        };
+
+        let body = if response.body.is_null() { String::new() } else { response.body.to_string() };
+
+        let _ = request.respond(
+            tiny_http::Response::from_string(body)
+                .with_status_code(response.status)
+                .with_header(content_type.clone()),
+        );
+    }
+
+    Ok(())
+}
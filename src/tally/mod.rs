@@ -0,0 +1,100 @@
+use serde::Serialize;
+use std::cmp::Reverse;
+use uuid::Uuid;
+
+use crate::poll::PollOption;
+
+// A ranked vote: the options in order of preference and how much the vote counts
+#[derive(Debug, PartialEq, Clone)]
+pub struct Ballot {
+    pub ranking: Vec<Uuid>,
+    pub weight: i64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct RoundTally {
+    pub option_id: Uuid,
+    pub label: String,
+    pub votes: i64,
+}
+
+// One instant runoff round: the options still in the race, the ballots with no option left
+// and the option eliminated at the end of the round, none on the last one
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Round {
+    pub tallies: Vec<RoundTally>,
+    pub exhausted: i64,
+    pub eliminated: Vec<Uuid>,
+}
+
+impl Round {
+    // Votes still counting in this round, exhausted ballots left out
+    pub fn continuing(&self) -> i64 {
+        self.tallies.iter().map(|tally| tally.votes).sum()
+    }
+
+    // The option with more than half of the continuing votes, if any
+    pub fn majority(&self) -> Option<&RoundTally> {
+        self.tallies.iter().find(|tally| tally.votes * 2 > self.continuing())
+    }
+}
+
+// Each ballot counts for its highest ranked option still in the race. Rounds go on until an option has
+// more than half of the continuing votes, eliminating the option with the fewest votes after each one.
+// Of options tied for fewest, the one that had fewer votes the round before goes, and then the one listed last.
+// When every option left has the same votes nobody can be eliminated and the runoff ends without a winner.
+pub fn instant_runoff(options: &[PollOption], ballots: &[Ballot]) -> Vec<Round> {
+    let mut continuing: Vec<&PollOption> = options.iter().collect();
+    let mut rounds = Vec::new();
+
+    if ballots.is_empty() {
+        return rounds;
+    }
+
+    while !continuing.is_empty() {
+        let mut tallies: Vec<RoundTally> = continuing
+            .iter()
+            .map(|option| RoundTally { option_id: option.id, label: option.label.clone(), votes: 0 })
+            .collect();
+        let mut exhausted = 0;
+
+        for ballot in ballots {
+            let preferred = ballot.ranking.iter().find_map(|option_id| tallies.iter().position(|tally| tally.option_id == *option_id));
+
+            match preferred {
+                Some(position) => tallies[position].votes += ballot.weight,
+                None => exhausted += ballot.weight,
+            }
+        }
+
+        let mut round = Round { tallies, exhausted, eliminated: Vec::new() };
+
+        let fewest = round.tallies.iter().map(|tally| tally.votes).min().unwrap_or_default();
+        let most = round.tallies.iter().map(|tally| tally.votes).max().unwrap_or_default();
+
+        if round.majority().is_some() || fewest == most {
+            rounds.push(round);
+            break;
+        }
+
+        let previous_votes = |option_id: Uuid| {
+            rounds.last()
+                .and_then(|previous: &Round| previous.tallies.iter().find(|tally| tally.option_id == option_id))
+                .map(|tally| tally.votes)
+                .unwrap_or_default()
+        };
+
+        // The tallies are in the order of the options, so a later one is listed later
+        round.eliminated = round.tallies.iter()
+            .enumerate()
+            .filter(|(_, tally)| tally.votes == fewest)
+            .min_by_key(|(position, tally)| (previous_votes(tally.option_id), Reverse(*position)))
+            .map(|(_, tally)| tally.option_id)
+            .into_iter()
+            .collect();
+        continuing.retain(|option| !round.eliminated.contains(&option.id));
+        rounds.push(round);
+    }
+
+    rounds
+}
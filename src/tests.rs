@@ -1,3 +1,77 @@
+#[cfg(test)]
+mod helpers {
+    use rusqlite::{Connection, Result};
+    use uuid::Uuid;
+
+    use crate::poll::{Poll, PollOption};
+    use crate::user::{Role, User};
+    use crate::voter;
+    use crate::voter::Voter;
+
+    pub fn insert_poll(conn: &Connection, poll: &Poll) -> Result<()> {
+        conn.execute(
+            "INSERT INTO Poll (id, question, poll_duration, create_date, start_date, expiration_date, abstentions, quorum, threshold, status, status_reason, anonymous)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            [
+                &poll.id.to_string(),
+                &poll.question,
+                &poll.poll_duration.to_string(),
+                &poll.create_date.to_string(),
+                &poll.start_date.to_string(),
+                &poll.expiration_date.to_string(),
+                &poll.abstentions.to_string(),
+                &poll.rules.quorum.to_string(),
+                &poll.rules.threshold.to_string(),
+                &poll.status.to_string(),
+                &poll.status_reason,
+                &(poll.rules.anonymous as i64).to_string(),
+            ],
+        )?;
+
+        for (position, option) in poll.options.iter().enumerate() {
+            conn.execute(
+                "INSERT INTO PollOption (id, poll_id, label, position, votes) VALUES (?1, ?2, ?3, ?4, ?5)",
+                (option.id.to_string(), option.poll_id.to_string(), &option.label, position as i64, option.votes),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn test_voter(conn: &Connection, name: &str) -> Voter {
+        voter::create_voter(conn, name.to_string()).expect("Voter to be created")
+    }
+
+    // An account without a password, for tests that only need someone to act as
+    pub fn test_user(conn: &Connection, name: &str) -> User {
+        let voter = test_voter(conn, name);
+        let user = User { id: Uuid::new_v4(), name: voter.name, create_date: voter.create_date, voter_id: voter.id, role: Role::Admin };
+
+        conn.execute(
+            "INSERT INTO User (id, name, password_hash, create_date, voter_id, role) VALUES (?1, ?2, '', ?3, ?4, ?5)",
+            (user.id.to_string(), &user.name, user.create_date, user.voter_id.to_string(), user.role),
+        ).expect("User to be created");
+
+        user
+    }
+
+    pub fn yes_no() -> Vec<String> {
+        vec!["Yes".to_string(), "No".to_string()]
+    }
+
+    pub fn yes_no_options(poll_id: Uuid) -> Vec<PollOption> {
+        yes_no()
+            .into_iter()
+            .map(|label| PollOption {
+                id: Uuid::new_v4(),
+                poll_id,
+                label,
+                votes: 0,
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod polls {
     use rusqlite::{Connection, Result};
@@ -8,7 +82,13 @@ mod polls {
     use crate::poll;
     use crate::poll::Poll;
     use crate::poll::PollDuration;
-    use crate::poll::ValidationError;
+    use crate::poll::{Outcome, PollKind, PollRules, PollStatus, Threshold};
+    use crate::error::PollError;
+    use crate::vote;
+    use crate::vote::VoteChoice;
+    use crate::voter;
+    use super::helpers::{insert_poll, test_user, test_voter, yes_no, yes_no_options};
+    use chrono::Duration;
 
     #[test]
     fn test_get_polls() -> Result<()> {
@@ -19,53 +99,49 @@ mod polls {
     
         let now = Local::now().timestamp();
 
+        let poll1_id = Uuid::new_v4();
+
         let poll1 = Poll {
-            id: Uuid::new_v4(),
+            id: poll1_id,
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: yes_no_options(poll1_id),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let expected_polls = vec![&poll1, &poll2];
+        let expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
 
         println!("Insert okay");
 
@@ -96,14 +172,22 @@ mod polls {
         let poll = Poll {
             id: Uuid::new_v4(),
             question: "Do You like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
         
-        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "30".to_string());
+        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "30".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
 
         println!("{:?}", poll);
         println!("{:?}", poll_output);
@@ -114,8 +198,9 @@ mod polls {
                 assert_eq!(poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(poll.create_date, poll_generated.create_date);
                 assert_eq!(poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(poll.negative_votes, poll_generated.negative_votes);
+                assert_eq!(poll_generated.options.len(), 2);
+                assert_eq!(poll_generated.options[0].label, "Yes");
+                assert_eq!(poll_generated.options[1].label, "No");
             }
             Err(e) => {
                 panic!("Error: {}", e);
@@ -136,14 +221,22 @@ mod polls {
         let poll = Poll {
             id: Uuid::new_v4(),
             question: "Do You like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
         
-        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string());
+        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
 
         println!("{:?}", poll);
         println!("{:?}", poll_output);
@@ -154,8 +247,6 @@ mod polls {
                 assert_eq!(poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(poll.create_date, poll_generated.create_date);
                 assert_eq!(poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(poll.negative_votes, poll_generated.negative_votes);
             }
             Err(e) => {
                 panic!("Error: {}", e);
@@ -171,23 +262,16 @@ mod polls {
     
         create_tables(&conn)?;
         
-        let poll_output = poll::create_poll(&conn, "A".repeat(151).to_string(), "7".to_string());
+        let poll_output = poll::create_poll(&conn, "A".repeat(151).to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
 
-        let expected_error =  ValidationError::new(
-            "Question is too long. Question only can have up to 150 chars.",
-        );
+        let expected_error = PollError::QuestionTooLong { max: 150 };
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -200,23 +284,16 @@ mod polls {
     
         create_tables(&conn)?;
         
-        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "5".to_string());
+        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "5x".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
 
-        let expected_error =  ValidationError::new(
-            "Invalid input for Poll Duration. Must be 7 or 30.",
-        );
+        let expected_error = PollError::InvalidDuration;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -230,23 +307,16 @@ mod polls {
         create_tables(&conn)?;
     
         
-        let poll_output = poll::create_poll(&conn, "".to_string(), "5".to_string());
+        let poll_output = poll::create_poll(&conn, "".to_string(), "5".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
 
-        let expected_error =  ValidationError::new(
-            "Question can't be empty.",
-        );
+        let expected_error = PollError::QuestionEmpty;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -264,62 +334,64 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "1".to_string(), "y".to_string(), "Do You Like C++?".to_string(), "30".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll1.id, &test_user(&conn, "Ana"), "y".to_string(), "Do You Like C++?".to_string(), "30".to_string());
 
         let expected_poll = Poll {
             id: Uuid::new_v4(),
             question: "Do You Like C++?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         println!("{:?}", expected_poll);
@@ -331,8 +403,6 @@ mod polls {
                 assert_eq!(expected_poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(expected_poll.create_date, poll_generated.create_date);
                 assert_eq!(expected_poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(expected_poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(expected_poll.negative_votes, poll_generated.negative_votes);
                 Ok(())
             }
             Err(e) => {
@@ -353,62 +423,64 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
 
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "y".to_string(), "Do You Like C++?".to_string(), "7".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "y".to_string(), "Do You Like C++?".to_string(), "7".to_string());
 
         let expected_poll = Poll {
             id: Uuid::new_v4(),
             question: "Do You Like C++?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         println!("{:?}", expected_poll);
@@ -420,8 +492,6 @@ mod polls {
                 assert_eq!(expected_poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(expected_poll.create_date, poll_generated.create_date);
                 assert_eq!(expected_poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(expected_poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(expected_poll.negative_votes, poll_generated.negative_votes);
                 Ok(())
             }
             Err(e) => {
@@ -442,62 +512,64 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "n".to_string(), "Do You Like C++?".to_string(), "".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "n".to_string(), "Do You Like C++?".to_string(), "".to_string());
 
         let expected_poll = Poll {
             id: Uuid::new_v4(),
             question: "Do You Like C++?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: poll1.create_date,
+            start_date: poll1.create_date,
             expiration_date : poll2.expiration_date,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         println!("{:?}", expected_poll);
@@ -509,8 +581,6 @@ mod polls {
                 assert_eq!(expected_poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(expected_poll.create_date, poll_generated.create_date);
                 assert_eq!(expected_poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(expected_poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(expected_poll.negative_votes, poll_generated.negative_votes);
                 Ok(())
             }
             Err(e) => {
@@ -526,30 +596,24 @@ mod polls {
     
         create_tables(&conn)?;
         
-        let poll_output = poll::edit_poll(&conn, "1".to_string(), "y".to_string(), "".to_string(), "7".to_string());
+        let unknown_id = Uuid::new_v4();
+        let poll_output = poll::edit_poll(&conn, &unknown_id, &test_user(&conn, "Ana"), "y".to_string(), "".to_string(), "7".to_string());
 
-        let expected_error =  ValidationError::new(
-            "There are no polls to Edit.",
-        );
+        let expected_error = PollError::PollNotFound(unknown_id.to_string());
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
     }
 
     #[test]
-    fn test_edit_poll_invalid_poll_index() -> Result<()> {
+    fn test_edit_poll_unknown_id() -> Result<()> {
         println!("Starting Test");
         let conn = Connection::open_in_memory()?;
     
@@ -560,69 +624,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
-    
-        
-        let poll_output = poll::edit_poll(&conn, "3".to_string(), "".to_string(), "".to_string(), "".to_string());
+        insert_poll(&conn, &poll2)?;
 
-        let expected_error =  ValidationError::new(
-            "Invalid input for selecting Poll. Please enter a valid number.",
-        );
+        let unknown_id = Uuid::new_v4();
+        let poll_output = poll::edit_poll(&conn, &unknown_id, &test_user(&conn, "Ana"), "".to_string(), "".to_string(), "".to_string());
+
+        let expected_error = PollError::PollNotFound(unknown_id.to_string());
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -640,69 +691,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "y".to_string(), "".to_string(), "7".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "y".to_string(), "".to_string(), "7".to_string());
 
-        let expected_error =  ValidationError::new(
-            "Question can't be empty.",
-        );
+        let expected_error = PollError::QuestionEmpty;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -720,69 +758,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "y".to_string(), "A".repeat(151).to_string(), "7".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "y".to_string(), "A".repeat(151).to_string(), "7".to_string());
 
-        let expected_error =  ValidationError::new(
-            "Question is too long. Question only can have up to 150 chars.",
-        );
+        let expected_error = PollError::QuestionTooLong { max: 150 };
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -800,69 +825,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "y".to_string(), "Do You Like C++?".to_string(), "5".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "y".to_string(), "Do You Like C++?".to_string(), "5x".to_string());
 
-        let expected_error =  ValidationError::new(
-            "Invalid input for Poll Duration. Please enter 7 or 30 Days.",
-        );
+        let expected_error = PollError::InvalidDuration;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -880,69 +892,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::edit_poll(&conn, "2".to_string(), "2".to_string(), "Do You Like C++?".to_string(), "7".to_string());
+        let poll_output = poll::edit_poll(&conn, &poll2.id, &test_user(&conn, "Ana"), "2".to_string(), "Do You Like C++?".to_string(), "7".to_string());
 
-        let expected_error =  ValidationError::new(
-            "Invalid input for choice 2. Please enter 'y' or 'n'.",
-        );
+        let expected_error = PollError::InvalidConfirmation;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -960,53 +959,47 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
     
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
     
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::delete_poll(&conn, "2".to_string(), "y".to_string());
+        let poll_output = poll::delete_poll(&conn, &poll2.id, "y".to_string());
     
         let expected_poll = poll2;
     
@@ -1020,8 +1013,6 @@ mod polls {
                 assert_eq!(expected_poll.poll_duration, poll_generated.poll_duration);
                 assert_eq!(expected_poll.create_date, poll_generated.create_date);
                 assert_eq!(expected_poll.expiration_date, poll_generated.expiration_date);
-                assert_eq!(expected_poll.positive_votes, poll_generated.positive_votes);
-                assert_eq!(expected_poll.negative_votes, poll_generated.negative_votes);
                 Ok(())
             }
             Err(e) => {
@@ -1037,32 +1028,25 @@ mod polls {
         let conn = Connection::open_in_memory()?;
     
         create_tables(&conn)?;
-    
-        
-        let poll_output = poll::delete_poll(&conn, "".to_string(), "".to_string());
 
-        let expected_error =  ValidationError::new(
-            "There are no polls to Delete.",
-        );
+        let unknown_id = Uuid::new_v4();
+        let poll_output = poll::delete_poll(&conn, &unknown_id, "y".to_string());
+
+        let expected_error = PollError::PollNotFound(unknown_id.to_string());
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
     }
 
     #[test]
-    fn test_delete_poll_invalid_poll_index() -> Result<()> {
+    fn test_delete_poll_unknown_id() -> Result<()> {
         println!("Starting Test");
         let conn = Connection::open_in_memory()?;
     
@@ -1073,69 +1057,56 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
-    
-        
-        let poll_output = poll::delete_poll(&conn, "3".to_string(), "".to_string());
+        insert_poll(&conn, &poll2)?;
 
-        let expected_error =  ValidationError::new(
-            "Invalid input for selecting Poll. Please enter a valid number.",
-        );
+        let unknown_id = Uuid::new_v4();
+        let poll_output = poll::delete_poll(&conn, &unknown_id, "y".to_string());
+
+        let expected_error = PollError::PollNotFound(unknown_id.to_string());
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
@@ -1153,493 +1124,2390 @@ mod polls {
         let poll1 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Rust?".trim().to_string(),
-            poll_duration: PollDuration::OneWeek,
+            poll_duration: PollDuration::Days(7),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*7,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
         let poll2 = Poll {
             id: Uuid::new_v4(),
             question: "Do you like Python?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
+            poll_duration: PollDuration::Days(30),
             create_date: now,
+            start_date: now,
             expiration_date : now + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+            options: Vec::new(),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
 
-        let _expected_polls = vec![&poll1, &poll2];
+        let _expected_polls = [&poll1, &poll2];
     
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll1.id.to_string(),
-                &poll1.question,
-                &poll1.poll_duration.to_string(),
-                &poll1.create_date.to_string(),
-                &poll1.expiration_date.to_string(),
-                &poll1.positive_votes.to_string(),
-                &poll1.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll1)?;
 
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll2.id.to_string(),
-                &poll2.question,
-                &poll2.poll_duration.to_string(),
-                &poll2.create_date.to_string(),
-                &poll2.expiration_date.to_string(),
-                &poll2.positive_votes.to_string(),
-                &poll2.negative_votes.to_string(),
-            ],
-        )?;
+        insert_poll(&conn, &poll2)?;
     
         
-        let poll_output = poll::delete_poll(&conn, "2".to_string(), "n".to_string());
+        let poll_output = poll::delete_poll(&conn, &poll2.id, "n".to_string());
 
-        let expected_error =  ValidationError::new(
-            "Canceling operation. Not a Valid Confirmation.",
-        );
+        let expected_error = PollError::NotConfirmed;
 
         match poll_output {
             Err(err) => {
                 println!("Output error: {:?}", err);
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
-                }
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
             }
             Ok(_) => panic!("Expected Error."),
         }       
     }
-}
-
-#[cfg(test)]
-mod votes {
-    use rusqlite::{Connection, Result};
-    
-    use uuid::Uuid;
-    use chrono::Local;
-    
-    use crate::create_tables;
-    
-    use crate::vote;
-    use crate::vote::Vote;
-    use crate::vote::VoteChoice;
-    use crate::vote::ValidationError;
-    use crate::poll::PollDuration;
-    use crate::poll;
-    use crate::poll::Poll;
-
 
     #[test]
-    fn test_get_votes() -> Result<()> {
+    fn test_create_poll_custom_options() -> Result<()> {
         let conn = Connection::open_in_memory()?;
-    
+
         create_tables(&conn)?;
 
-        let poll = Poll {
-            id: Uuid::new_v4(),
-            question: "teste question?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
-            create_date: Local::now().timestamp(),
-            expiration_date : Local::now().timestamp() + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
-        };
-    
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll.id.to_string(),
-                &poll.question,
-                &poll.poll_duration.to_string(),
-                &poll.create_date.to_string(),
-                &poll.expiration_date.to_string(),
-                &poll.positive_votes.to_string(),
-                &poll.negative_votes.to_string(),
-            ],
-        )?;
+        let options = vec!["Rust".to_string(), " Go ".to_string(), "Zig".to_string()];
 
-        let vote = Vote {
-            id: Uuid::new_v4(),
-            choice: match "y".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "test".trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question,
-        };
+        let poll_output = poll::create_poll(&conn, "Best language?".to_string(), "7".to_string(), options, "".to_string(), PollRules::default(), PollKind::Single, None);
 
-        let choice = match &vote.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
+        let poll_generated = match poll_output {
+            Ok(poll_generated) => poll_generated,
+            Err(e) => panic!("Error: {}", e),
         };
 
-        conn.execute(
-            "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            &[
-                &vote.id.to_string(),
-                &choice,
-                &vote.comment,
-                &vote.voting_power.to_string(),
-                &vote.create_date.to_string(),
-                &vote.poll_id.to_string(),
-            ]
-        )?;
-    
-        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls.len(), 1);
+        assert_eq!(polls[0], poll_generated);
+        assert_eq!(polls[0].options.iter().map(|option| option.label.as_str()).collect::<Vec<_>>(), vec!["Rust", "Go", "Zig"]);
+        assert!(polls[0].options.iter().all(|option| option.votes == 0 && option.poll_id == poll_generated.id));
 
-        assert_eq!(votes.len(), 1);
-        assert_eq!(votes[0].id, vote.id);
-        assert_eq!(votes[0].choice, match choice.as_str() {
-            "y" => VoteChoice::Yes,
-            "n" => VoteChoice::No,
-            _ => panic!("Invalid Vote"),
-        });
-        assert_eq!(votes[0].poll_question, vote.poll_question);
-        assert_eq!(votes[0].poll_id, poll.id);
-    
         Ok(())
     }
 
     #[test]
-    fn test_delete_vote() -> Result<()> {
+    fn test_create_poll_invalid_options() -> Result<()> {
         let conn = Connection::open_in_memory()?;
-    
+
         create_tables(&conn)?;
 
-        let poll = Poll {
-            id: Uuid::new_v4(),
-            question: "teste question?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
-            create_date: Local::now().timestamp(),
-            expiration_date : Local::now().timestamp() + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
-        };
-    
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll.id.to_string(),
-                &poll.question,
-                &poll.poll_duration.to_string(),
-                &poll.create_date.to_string(),
-                &poll.expiration_date.to_string(),
-                &poll.positive_votes.to_string(),
-                &poll.negative_votes.to_string(),
-            ],
-        )?;
+        let cases = [
+            (vec!["Only one".to_string()], PollError::TooFewOptions { min: 2 }),
+            (vec!["Yes".to_string(), " ".to_string()], PollError::OptionEmpty),
+            (vec!["Yes".to_string(), "yes".to_string()], PollError::DuplicateOptions),
+            (vec!["Yes".to_string(), "A".repeat(51)], PollError::OptionTooLong { max: 50 }),
+            ((1..=11).map(|i| i.to_string()).collect(), PollError::TooManyOptions { max: 10 }),
+        ];
 
-        let vote = Vote {
-            id: Uuid::new_v4(),
-            choice: match "y".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "test".trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question,
-        };
+        for (options, expected_error) in cases {
+            let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string(), options, "".to_string(), PollRules::default(), PollKind::Single, None);
 
-        let choice = match &vote.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
-        };
+            match poll_output {
+                Err(err) => {
+                    assert_eq!(err, expected_error, "Different Errors");
+                }
+                Ok(_) => panic!("Expected Error."),
+            }
+        }
 
-        conn.execute(
-            "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            &[
-                &vote.id.to_string(),
-                &choice,
-                &vote.comment,
-                &vote.voting_power.to_string(),
-                &vote.create_date.to_string(),
-                &vote.poll_id.to_string(),
-            ]
-        )?;
-    
-        let votes = vote::get_votes(&conn)?;
+        assert!(poll::get_polls(&conn)?.is_empty());
 
-        assert_eq!(votes.len(), 1);
-        assert_eq!(votes[0].id, vote.id);
-        assert_eq!(votes[0].choice, vote.choice);
+        Ok(())
+    }
 
-        let _ = vote::delete_vote(&conn, &votes[0]);
+    #[test]
+    fn test_find_option() {
+        let poll_id = Uuid::new_v4();
 
-        let votes = vote::get_votes(&conn)?;
+        let poll = Poll {
+            id: poll_id,
+            question: "Do you like Rust?".to_string(),
+            poll_duration: PollDuration::Days(7),
+            create_date: 0,
+            start_date: 0,
+            expiration_date: 24*60*60*7,
+            options: yes_no_options(poll_id),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
+        };
 
-        assert_eq!(votes.len(), 0);
+        assert_eq!(poll.find_option("yes"), Some(&poll.options[0]));
+        assert_eq!(poll.find_option(" NO\n"), Some(&poll.options[1]));
+        assert_eq!(poll.find_option("2"), Some(&poll.options[1]));
+        assert_eq!(poll.find_option("3"), None);
+        assert_eq!(poll.find_option("maybe"), None);
+    }
+
+    #[test]
+    fn test_parse_poll_duration() {
+        assert_eq!("36h".parse::<PollDuration>(), Ok(PollDuration::Hours(36)));
+        assert_eq!(" 3D ".parse::<PollDuration>(), Ok(PollDuration::Days(3)));
+        assert_eq!("7".parse::<PollDuration>(), Ok(PollDuration::Days(7)));
+
+        let end_of_day = poll::parse_date("2030-01-01").unwrap() + 24*60*60;
+        assert_eq!("2030-01-01".parse::<PollDuration>(), Ok(PollDuration::Until(end_of_day)));
+
+        let exact_date = poll::parse_date("2030-01-01 18:30").unwrap();
+        assert_eq!("2030-01-01T18:30".parse::<PollDuration>(), Ok(PollDuration::Until(exact_date)));
+
+        for invalid in ["", "0d", "-1h", "abc", "3w", "2030-13-01"] {
+            assert!(invalid.parse::<PollDuration>().is_err(), "{} should be invalid", invalid);
+        }
+
+        // Every duration can be read back from the text stored in the database
+        for duration in [PollDuration::Hours(36), PollDuration::Days(3), PollDuration::Until(exact_date)] {
+            assert_eq!(duration.to_string().parse::<PollDuration>(), Ok(duration));
+        }
+    }
+
+    #[test]
+    fn test_parse_threshold() {
+        assert_eq!("".parse::<Threshold>(), Ok(Threshold::Majority));
+        assert_eq!(" Majority ".parse::<Threshold>(), Ok(Threshold::Majority));
+        assert_eq!("2/3".parse::<Threshold>(), Ok(Threshold::AtLeast(2, 3)));
+        assert_eq!("60%".parse::<Threshold>(), Ok(Threshold::AtLeast(60, 100)));
+
+        for invalid in ["0/3", "4/3", "0%", "101%", "half", "2/0"] {
+            assert_eq!(invalid.parse::<Threshold>(), Err(PollError::InvalidThreshold), "{} should be invalid", invalid);
+        }
+
+        for threshold in [Threshold::Majority, Threshold::AtLeast(2, 3), Threshold::AtLeast(60, 100)] {
+            assert_eq!(threshold.to_string().parse::<Threshold>(), Ok(threshold));
+        }
+    }
+
+    #[test]
+    fn test_poll_revisions() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let created = poll::create_poll(&conn, "Lunch?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        assert!(poll::get_poll_revisions(&conn, &created.id)?.is_empty());
+
+        poll::edit_poll(&conn, &created.id, &test_user(&conn, "Ana"), "n".to_string(), "Lunch today?".to_string(), "".to_string()).expect("Poll to be edited");
+        poll::edit_poll(&conn, &created.id, &test_user(&conn, "Bruno"), "y".to_string(), "Lunch tomorrow?".to_string(), "3d".to_string()).expect("Poll to be edited");
+
+        // Each revision keeps the poll as it was before the edit, and who changed it
+        let revisions = poll::get_poll_revisions(&conn, &created.id)?;
+
+        assert_eq!(revisions.len(), 2);
+        assert_eq!((revisions[0].question.as_str(), revisions[0].editor_name.as_str()), ("Lunch?", "Ana"));
+        assert_eq!(revisions[0].poll_duration, PollDuration::Days(7));
+        assert_eq!(revisions[0].expiration_date, created.expiration_date);
+        assert_eq!((revisions[1].question.as_str(), revisions[1].editor_name.as_str()), ("Lunch today?", "Bruno"));
+
+        // A failed edit leaves no revision behind
+        assert_eq!(
+            poll::edit_poll(&conn, &created.id, &test_user(&conn, "Carla"), "y".to_string(), "".to_string(), "3d".to_string()).err(),
+            Some(PollError::QuestionEmpty)
+        );
+        assert_eq!(poll::get_poll_revisions(&conn, &created.id)?.len(), 2);
+
+        // Editing records the editor, it doesn't make voters
+        assert_eq!(voter::get_voters(&conn)?.len(), 3);
+
+        poll::delete_poll_by_id(&conn, &created.id).expect("Poll to be deleted");
+
+        assert!(poll::get_poll_revisions(&conn, &created.id)?.is_empty());
 
         Ok(())
-        
     }
 
     #[test]
-    fn test_edit_vote() -> Result<()> {
+    fn test_close_and_reopen_poll() -> Result<()> {
         let conn = Connection::open_in_memory()?;
-    
+
         create_tables(&conn)?;
 
-        let poll = Poll {
-            id: Uuid::new_v4(),
-            question: "teste question?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
-            create_date: Local::now().timestamp(),
-            expiration_date : Local::now().timestamp() + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
+        let now = Local::now().timestamp();
+        let id = Uuid::new_v4();
+        let created = Poll {
+            id,
+            question: "Lunch?".to_string(),
+            poll_duration: PollDuration::Days(7),
+            create_date: now - 24*60*60,
+            start_date: now - 24*60*60,
+            expiration_date: now + 6*24*60*60,
+            options: yes_no_options(id),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
         };
-    
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll.id.to_string(),
-                &poll.question,
-                &poll.poll_duration.to_string(),
-                &poll.create_date.to_string(),
-                &poll.expiration_date.to_string(),
-                &poll.positive_votes.to_string(),
-                &poll.negative_votes.to_string(),
-            ],
-        )?;
 
-        let vote = Vote {
-            id: Uuid::new_v4(),
-            choice: match "y".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "test".trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question,
-        };
+        insert_poll(&conn, &created)?;
+
+        let closed = poll::set_poll_status(&conn, &id, PollStatus::Closed, " Decided in person ".to_string()).expect("Poll to be closed");
+
+        assert!(closed.is_closed());
+        assert_eq!(closed.status_reason, "Decided in person");
+        assert_eq!(
+            vote::create_vote(&conn, closed.clone(), &test_voter(&conn, "Ana"), VoteChoice::Option(closed.options[0].id), String::new()).err(),
+            Some(PollError::PollNotOpen { poll_id: id, status: PollStatus::Closed })
+        );
+
+        // Nothing but the status changes, and extending the poll keeps its creation date too
+        let reopened = poll::set_poll_status(&conn, &id, PollStatus::Open, String::new()).expect("Poll to be reopened");
+
+        assert!(reopened.is_open());
+        assert_eq!((reopened.create_date, reopened.expiration_date), (created.create_date, created.expiration_date));
+
+        let edited = poll::edit_poll(&conn, &id, &test_user(&conn, "Carla"), "y".to_string(), "Lunch today?".to_string(), "3d".to_string()).expect("Poll to be edited");
+
+        assert_eq!(edited.create_date, created.create_date);
+        assert_eq!(poll::get_poll(&conn, &id)?.map(|poll| poll.create_date), Some(created.create_date));
+
+        vote::create_vote(&conn, reopened.clone(), &test_voter(&conn, "Bruno"), VoteChoice::Option(reopened.options[0].id), String::new())
+            .expect("Vote to be created");
+
+        assert_eq!(poll::set_poll_status(&conn, &id, PollStatus::Draft, String::new()).err(), Some(PollError::PollHasVotes));
+        assert_eq!(
+            poll::set_poll_status(&conn, &id, PollStatus::Archived, String::new()).err(),
+            Some(PollError::InvalidStatusChange { from: PollStatus::Open, to: PollStatus::Archived })
+        );
+
+        poll::set_poll_status(&conn, &id, PollStatus::Closed, String::new()).expect("Poll to be closed");
+        poll::set_poll_status(&conn, &id, PollStatus::Archived, "Old".to_string()).expect("Poll to be archived");
+
+        assert_eq!(
+            poll::set_poll_status(&conn, &id, PollStatus::Open, String::new()).err(),
+            Some(PollError::InvalidStatusChange { from: PollStatus::Archived, to: PollStatus::Open })
+        );
+        assert_eq!(poll::get_poll(&conn, &id)?.map(|poll| (poll.status, poll.status_reason)), Some((PollStatus::Archived, "Old".to_string())));
 
-        let choice = match &vote.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_outcome() {
+        let now = Local::now().timestamp();
+        let id = Uuid::new_v4();
+
+        let closed_poll = |yes: i64, no: i64, abstentions: i64, rules: PollRules| {
+            let mut options = yes_no_options(id);
+            options[0].votes = yes;
+            options[1].votes = no;
+
+            Poll {
+                id,
+                question: "Change the bylaws?".to_string(),
+                poll_duration: PollDuration::Days(7),
+                create_date: now - 24*60*60*8,
+                start_date: now - 24*60*60*8,
+                expiration_date: now - 24*60*60,
+                options,
+                abstentions,
+                ballots: yes + no,
+                rules,
+                kind: PollKind::Single,
+                status: PollStatus::Open,
+                status_reason: String::new(),
+                creator_id: None,
+                runoff: Vec::new(),
+            }
         };
 
-        conn.execute(
-            "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id) 
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            &[
-                &vote.id.to_string(),
-                &choice,
-                &vote.comment,
-                &vote.voting_power.to_string(),
-                &vote.create_date.to_string(),
-                &vote.poll_id.to_string(),
-            ]
-        )?;
-    
-        let votes = vote::get_votes(&conn)?;
+        let supermajority = PollRules { quorum: 20, threshold: Threshold::AtLeast(2, 3), anonymous: false };
 
-        assert_eq!(votes.len(), 1);
-        assert_eq!(votes[0].id, vote.id);
-        assert_eq!(votes[0].choice, vote.choice);
+        assert_eq!(closed_poll(6, 4, 0, PollRules::default()).outcome(), Outcome::Passed);
+        assert_eq!(closed_poll(5, 5, 0, PollRules::default()).outcome(), Outcome::Rejected);
+        assert_eq!(closed_poll(0, 0, 3, PollRules::default()).outcome(), Outcome::Rejected);
 
-        let _ = vote::edit_vote(&conn, &votes[0], &votes[0], "n".to_string(), "new comment".to_string());
+        // Abstentions count for the quorum but not for the threshold
+        assert_eq!(closed_poll(14, 7, 0, supermajority).outcome(), Outcome::Passed);
+        assert_eq!(closed_poll(12, 4, 4, supermajority).outcome(), Outcome::Passed);
+        assert_eq!(closed_poll(12, 7, 1, supermajority).outcome(), Outcome::Rejected);
+        assert_eq!(closed_poll(12, 4, 0, supermajority).outcome(), Outcome::NoQuorum);
 
-        let votes = vote::get_votes(&conn)?;
+        let results = closed_poll(3, 12, 5, supermajority);
 
-        assert_eq!(votes.len(), 1);
-        assert_eq!(votes[0].id, vote.id);
-        assert_eq!(votes[0].choice, VoteChoice::No);
+        assert_eq!(results.results().outcome, Outcome::Passed);
+        assert_eq!(results.results().winner, Some("No"));
+
+        let mut open_poll = closed_poll(14, 7, 0, supermajority);
+        open_poll.expiration_date = now + 24*60*60;
+
+        assert_eq!(open_poll.outcome(), Outcome::Pending);
+        assert_eq!(open_poll.results().winner, None);
+    }
+
+    #[test]
+    fn test_create_poll_with_rules() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let rules = PollRules { quorum: 20, threshold: Threshold::AtLeast(2, 3), anonymous: false };
+
+        let poll_generated = poll::create_poll(&conn, "Change the bylaws?".to_string(), "7".to_string(), yes_no(), "".to_string(), rules, PollKind::Single, None)
+            .expect("Poll to be created");
+
+        assert_eq!(poll_generated.rules, rules);
+        assert_eq!(poll::get_polls(&conn)?[0].rules, rules);
+
+        let poll_output = poll::create_poll(&conn, "Change the bylaws?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules { quorum: -1, threshold: Threshold::Majority, anonymous: false }, PollKind::Single, None);
+
+        assert_eq!(poll_output.err(), Some(PollError::InvalidQuorum));
 
         Ok(())
     }
 
     #[test]
-    fn test_create_vote() -> Result<()> {
+    fn test_create_poll_hours_duration() -> Result<()> {
         let conn = Connection::open_in_memory()?;
-        
+
         create_tables(&conn)?;
-    
-        let poll = Poll {
-            id: Uuid::new_v4(),
-            question: "teste question?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
-            create_date: Local::now().timestamp(),
-            expiration_date: Local::now().timestamp() + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
-        };
-        
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll.id.to_string(),
-                &poll.question,
-                &poll.poll_duration.to_string(),
-                &poll.create_date.to_string(),
-                &poll.expiration_date.to_string(),
-                &poll.positive_votes.to_string(),
-                &poll.negative_votes.to_string(),
-            ],
-        )?;
-    
-        let vote_1 = Vote {
-            id: Uuid::new_v4(),
-            choice: match "y".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "test".trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question.clone(),
-        };
-    
-        let choice = match &vote_1.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
-        };
-    
-        let polls = poll::get_polls(&conn)?;
-        
-        assert_eq!(polls[0].positive_votes, 0);
-        
-        let _ = vote::create_vote(&conn, poll.clone(), &choice, vote_1.comment); // Clone poll here too
-    
-        let votes = vote::get_votes(&conn)?;
-        let polls = poll::get_polls(&conn)?;
-        
-        assert_eq!(votes.len(), 1);
-        assert_eq!(votes[0].choice, vote_1.choice);
-        assert_eq!(votes[0].poll_question, poll.question);
-        assert_eq!(votes[0].poll_id, poll.id);
-        assert_eq!(polls[0].positive_votes, 1);
-        
-        let vote_2 = Vote {
-            id: Uuid::new_v4(),
-            choice: match "n".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "testing".trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question.clone(),
-        };
-    
-        let choice = match &vote_2.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
-        };
-    
-        let _ = vote::create_vote(&conn, poll.clone(), &choice, vote_2.comment); // Clone poll again here
-    
-        let votes = vote::get_votes(&conn)?;
+
+        let poll_generated = poll::create_poll(&conn, "Lunch at noon?".to_string(), "36h".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        assert_eq!(poll_generated.poll_duration, PollDuration::Hours(36));
+        assert_eq!(poll_generated.start_date, poll_generated.create_date);
+        assert_eq!(poll_generated.expiration_date, poll_generated.start_date + 36*60*60);
+        assert!(poll_generated.is_open());
+
         let polls = poll::get_polls(&conn)?;
-    
-        assert_eq!(votes.len(), 2);
-        assert_eq!(votes[1].choice, vote_2.choice);
-        assert_eq!(votes[1].poll_question, poll.question);
-        assert_eq!(polls[0].negative_votes, 1);
-    
+
+        assert_eq!(polls[0], poll_generated);
+
         Ok(())
     }
-    
 
     #[test]
-
-    fn test_comment_to_long() -> Result<()>{
+    fn test_create_poll_scheduled_start() -> Result<()> {
         let conn = Connection::open_in_memory()?;
-        
+
         create_tables(&conn)?;
-    
-        let poll = Poll {
-            id: Uuid::new_v4(),
-            question: "teste question?".trim().to_string(),
-            poll_duration: PollDuration::OneMonth,
-            create_date: Local::now().timestamp(),
-            expiration_date: Local::now().timestamp() + 24*60*60*30,
-            positive_votes: 0,
-            negative_votes: 0,
-        };
-        
-        conn.execute(
-            "INSERT INTO Poll (id, question, poll_duration, create_date, expiration_date, positive_votes, negative_votes ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            [
-                &poll.id.to_string(),
-                &poll.question,
-                &poll.poll_duration.to_string(),
-                &poll.create_date.to_string(),
-                &poll.expiration_date.to_string(),
-                &poll.positive_votes.to_string(),
-                &poll.negative_votes.to_string(),
-            ],
-        )?;
-    
-        let vote = Vote {
-            id: Uuid::new_v4(),
-            choice: match "y".trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: "test".repeat(151).trim().to_string(),
-            voting_power: 1,
-            create_date: Local::now().timestamp(),
-            poll_id: poll.id,
-            poll_question: poll.question.clone(),
-        };
-    
-        let choice = match &vote.choice {
-            VoteChoice::Yes => "y".to_string(),
-            VoteChoice::No => "n".to_string(),
-        };
-    
+
+        let start = Local::now() + Duration::days(3);
+        let input_start = start.format("%Y-%m-%d %H:%M").to_string();
+
+        let poll_generated = poll::create_poll(&conn, "Retro on Monday?".to_string(), "1d".to_string(), yes_no(), input_start.clone(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        assert_eq!(poll_generated.start_date, poll::parse_date(&input_start).unwrap());
+        assert_eq!(poll_generated.expiration_date, poll_generated.start_date + 24*60*60);
+        assert!(!poll_generated.has_started());
+        assert!(!poll_generated.is_open());
+        assert!(!poll_generated.is_closed());
+
         let polls = poll::get_polls(&conn)?;
-        
-        assert_eq!(polls[0].positive_votes, 0);
-        
-        let vote_output = vote::create_vote(&conn, poll.clone(), &choice, vote.comment); // Clone poll here too
-    
-        let expected_error =  ValidationError::new(
-            "Comment is too long. Comment only can have up to 100 chars.",
-        );
 
-        match vote_output {
-            Err(err) => {
-                println!("Output error: {:?}", err);
+        assert_eq!(polls[0], poll_generated);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_poll_invalid_dates() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let yesterday = (Local::now() - Duration::days(1)).format("%Y-%m-%d %H:%M").to_string();
+        let next_week = (Local::now() + Duration::days(7)).format("%Y-%m-%d").to_string();
+        let next_month = (Local::now() + Duration::days(30)).format("%Y-%m-%d").to_string();
 
-                // Tente converter o erro para ValidationError
-                if let Some(validation_error) = err.downcast_ref::<ValidationError>() {
-                    assert_eq!(validation_error, &expected_error, "Different Error Messages");
-                    Ok(())
-                } else {
-                    panic!("Expected ValidationError Type");
+        let cases = [
+            ("3d".to_string(), yesterday, PollError::StartDateInPast),
+            ("3d".to_string(), "next monday".to_string(), PollError::InvalidStartDate),
+            (next_week, next_month, PollError::EndsBeforeStart),
+        ];
+
+        for (input_duration, input_start, expected_error) in cases {
+            let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), input_duration, yes_no(), input_start, PollRules::default(), PollKind::Single, None);
+
+            match poll_output {
+                Err(err) => {
+                    assert_eq!(err, expected_error, "Different Errors");
                 }
+                Ok(_) => panic!("Expected Error."),
             }
-            Ok(_) => panic!("Expected Error."),
         }
 
+        assert!(poll::get_polls(&conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_poll_rolls_back_when_a_statement_fails() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll_generated = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        let voter = test_voter(&conn, "Ana");
+
+        let _ = vote::create_vote(&conn, poll_generated.clone(), &voter, VoteChoice::Option(poll_generated.options[0].id), "".to_string());
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail_poll_delete BEFORE DELETE ON Poll BEGIN SELECT RAISE(ABORT, 'poll failure'); END;",
+        )?;
+
+        assert!(poll::delete_poll(&conn, &poll_generated.id, "y".to_string()).is_err());
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls.len(), 1);
+        assert_eq!(polls[0].options.len(), 2);
+        assert_eq!(polls[0].options[0].votes, 1);
+        assert_eq!(vote::get_votes(&conn)?.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_poll_rolls_back_when_an_option_fails() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail_option BEFORE INSERT ON PollOption WHEN NEW.position = 1 BEGIN SELECT RAISE(ABORT, 'option failure'); END;",
+        )?;
+
+        let poll_output = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None);
+
+        assert!(poll_output.is_err());
+        assert!(poll::get_polls(&conn)?.is_empty());
+
+        let options: i64 = conn.query_row("SELECT COUNT(*) FROM PollOption", [], |row| row.get(0))?;
+        assert_eq!(options, 0);
+
+        Ok(())
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_verify_tallies() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll_generated = poll::create_poll(&conn, "Do You like Rust?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        let _ = vote::create_vote(&conn, poll_generated.clone(), &test_voter(&conn, "Ana"), VoteChoice::Option(poll_generated.options[0].id), "".to_string());
+        let _ = vote::create_vote(&conn, poll_generated.clone(), &test_voter(&conn, "Bruno"), VoteChoice::Option(poll_generated.options[0].id), "".to_string());
+
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        conn.execute(
+            "UPDATE PollOption SET votes = 5 WHERE id = ?1",
+            [poll_generated.options[1].id.to_string()],
+        )?;
+
+        conn.execute(
+            "UPDATE Poll SET abstentions = 2 WHERE id = ?1",
+            [poll_generated.id.to_string()],
+        )?;
+
+        let expected_mismatches = vec![
+            poll::TallyMismatch {
+                poll_id: poll_generated.id,
+                question: poll_generated.question.clone(),
+                choice: VoteChoice::Option(poll_generated.options[1].id),
+                label: "No".to_string(),
+                stored_votes: 5,
+                counted_votes: 0,
+            },
+            poll::TallyMismatch {
+                poll_id: poll_generated.id,
+                question: poll_generated.question.clone(),
+                choice: VoteChoice::Abstain,
+                label: "Abstain".to_string(),
+                stored_votes: 2,
+                counted_votes: 0,
+            },
+        ];
+
+        // Without repair the counters are only reported
+        assert_eq!(poll::verify_tallies(&conn, false)?, expected_mismatches);
+        assert_eq!(poll::get_polls(&conn)?[0].options[1].votes, 5);
+
+        assert_eq!(poll::verify_tallies(&conn, true)?, expected_mismatches);
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].options[0].votes, 2);
+        assert_eq!(polls[0].options[1].votes, 0);
+        assert_eq!(polls[0].abstentions, 0);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_poll_by_id_or_short_code() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let now = Local::now().timestamp();
+        let ids = [
+            Uuid::parse_str("aaaaaaaa-0000-4000-8000-000000000001").unwrap(),
+            Uuid::parse_str("aaaaaaaa-1111-4000-8000-000000000002").unwrap(),
+            Uuid::parse_str("bbbbbbbb-0000-4000-8000-000000000003").unwrap(),
+        ];
+
+        for id in ids {
+            insert_poll(&conn, &Poll {
+                id,
+                question: "Do you like Rust?".to_string(),
+                poll_duration: PollDuration::Days(7),
+                create_date: now,
+                start_date: now,
+                expiration_date: now + 24*60*60*7,
+                options: yes_no_options(id),
+                abstentions: 0,
+                ballots: 0,
+                rules: PollRules::default(),
+                kind: PollKind::Single,
+                status: PollStatus::Open,
+                status_reason: String::new(),
+                creator_id: None,
+                runoff: Vec::new(),
+            })?;
+        }
+
+        let by_id = poll::find_poll(&conn, &ids[1].to_string()).expect("Lookup to work").expect("Poll to be found");
+        assert_eq!(by_id.id, ids[1]);
+        assert_eq!(by_id.options.len(), 2);
+
+        let by_code = poll::find_poll(&conn, " BBBBBBBB ").expect("Lookup to work").expect("Poll to be found");
+        assert_eq!(by_code.id, ids[2]);
+        assert_eq!(by_code.short_code(), "bbbbbbbb");
+
+        let longer_code = poll::find_poll(&conn, "aaaaaaaa-1").expect("Lookup to work").expect("Poll to be found");
+        assert_eq!(longer_code.id, ids[1]);
+
+        assert!(poll::find_poll(&conn, "cccccccc").expect("Lookup to work").is_none());
+        assert!(poll::find_poll(&conn, "bbb").expect("Lookup to work").is_none());
+        assert!(poll::find_poll(&conn, "b%").expect("Lookup to work").is_none());
+
+        match poll::find_poll(&conn, "aaaaaaaa") {
+            Err(err) => {
+                assert_eq!(err, PollError::AmbiguousShortCode("aaaaaaaa".to_string()));
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_polls_keep_their_order_after_changes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let first = poll::create_poll(&conn, "First?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None).expect("Poll to be created");
+        let second = poll::create_poll(&conn, "Second?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None).expect("Poll to be created");
+
+        let listed = poll::get_polls(&conn)?;
+
+        // Another session edits the first poll and adds a new one after the list was shown
+        poll::edit_poll(&conn, &first.id, &test_user(&conn, "Ana"), "y".to_string(), "First, edited?".to_string(), "3d".to_string()).expect("Poll to be edited");
+        let third = poll::create_poll(&conn, "Third?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None).expect("Poll to be created");
+
+        let ids: Vec<Uuid> = poll::get_polls(&conn)?.iter().map(|poll| poll.id).collect();
+        assert_eq!(ids, [first.id, second.id, third.id]);
+
+        // The poll the user picked is the one removed, whatever happened in between
+        let deleted = poll::delete_poll(&conn, &listed[1].id, "y".to_string()).expect("Poll to be deleted");
+        assert_eq!(deleted.id, second.id);
+
+        let ids: Vec<Uuid> = poll::get_polls(&conn)?.iter().map(|poll| poll.id).collect();
+        assert_eq!(ids, [first.id, third.id]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_poll_error_messages() {
+        use std::error::Error;
+
+        assert_eq!(PollError::QuestionTooLong { max: 150 }.to_string(), "Question is too long. Question only can have up to 150 chars.");
+        assert_eq!(PollError::PollNotFound(Uuid::new_v4().to_string()).to_string(), "Poll not found.");
+        assert_eq!(PollError::InvalidChoice("Burger ".to_string()).to_string(), "Invalid Vote. \"Burger\" doesn't match the options of this poll.");
+        assert_eq!("5x".parse::<PollDuration>(), Err(PollError::InvalidDuration));
+
+        let database_error = PollError::from(rusqlite::Error::QueryReturnedNoRows);
+        assert!(matches!(database_error, PollError::Database(rusqlite::Error::QueryReturnedNoRows)));
+        assert!(database_error.source().is_some());
+    }
+}
+
+#[cfg(test)]
+mod votes {
+    use rusqlite::{Connection, Result};
+    
+    use uuid::Uuid;
+    use chrono::Local;
+    
+    use crate::create_tables;
+    
+    use crate::vote;
+    use crate::vote::{VoteAction, VoteChoice};
+    use crate::voter;
+    use crate::error::PollError;
+    use crate::poll::PollDuration;
+    use crate::poll::{PollKind, PollRules, PollStatus};
+    use crate::poll;
+    use crate::poll::Poll;
+    use super::helpers::{insert_poll, test_voter, yes_no_options};
+
+    fn test_poll() -> Poll {
+        let id = Uuid::new_v4();
+
+        Poll {
+            id,
+            question: "teste question?".trim().to_string(),
+            poll_duration: PollDuration::Days(30),
+            create_date: Local::now().timestamp(),
+            start_date: Local::now().timestamp(),
+            expiration_date : Local::now().timestamp() + 24*60*60*30,
+            options: yes_no_options(id),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+    
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+    
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let vote_id = Uuid::new_v4();
+
+        conn.execute(
+            "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id, voter_id) 
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            [
+                &vote_id.to_string(),
+                &poll.options[0].id.to_string(),
+                "test",
+                "1",
+                &Local::now().timestamp().to_string(),
+                &poll.id.to_string(),
+                &voter.id.to_string(),
+            ]
+        )?;
+    
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].id, vote_id);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+        assert_eq!(votes[0].option_label, "Yes");
+        assert_eq!(votes[0].poll_question, poll.question);
+        assert_eq!(votes[0].poll_id, poll.id);
+        assert_eq!(votes[0].voter_id, Some(voter.id));
+        assert_eq!(votes[0].voter_name, "Ana");
+    
+        Ok(())
+    }
+
+    #[test]
+    fn test_delete_vote() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+    
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+    
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let _ = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "test".to_string());
+    
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+
+        let _ = vote::delete_vote(&conn, &votes[0]);
+
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(votes.len(), 0);
+        assert_eq!(polls[0].options[0].votes, 0);
+
+        Ok(())
+        
+    }
+
+    #[test]
+    fn test_edit_vote() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+    
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+    
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let _ = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "test".to_string());
+    
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+
+        let edited_vote = vote::edit_vote(&conn, &votes[0], VoteChoice::Option(poll.options[1].id), "new comment".to_string());
+
+        match edited_vote {
+            Ok(edited_vote) => {
+                assert_eq!(edited_vote.option_label, "No");
+                assert_eq!(edited_vote.comment, "new comment");
+            }
+            Err(e) => panic!("Error: {}", e),
+        }
+
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[1].id));
+        assert_eq!(votes[0].comment, "new comment");
+        assert_eq!(polls[0].options[0].votes, 0);
+        assert_eq!(polls[0].options[1].votes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_vote() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+        
+        create_tables(&conn)?;
+    
+        let poll = test_poll();
+        
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+        let other_voter = test_voter(&conn, "Bruno");
+    
+        let polls = poll::get_polls(&conn)?;
+        
+        assert_eq!(polls[0].options[0].votes, 0);
+        
+        let _ = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "test".to_string());
+    
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+        
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+        assert_eq!(votes[0].poll_question, poll.question);
+        assert_eq!(votes[0].poll_id, poll.id);
+        assert_eq!(polls[0].options[0].votes, 1);
+    
+        let _ = vote::create_vote(&conn, poll.clone(), &other_voter, VoteChoice::Option(poll.options[1].id), "testing".to_string());
+    
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+    
+        assert_eq!(votes.len(), 2);
+        assert_eq!(votes[1].choice, VoteChoice::Option(poll.options[1].id));
+        assert_eq!(votes[1].poll_question, poll.question);
+        assert_eq!(polls[0].options[1].votes, 1);
+    
+        Ok(())
+    }
+
+    #[test]
+    fn test_weighted_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = voter::set_voter_weight(&conn, test_voter(&conn, "Ana").name, 3).expect("Weight to be set");
+        let other_voter = test_voter(&conn, "Bruno");
+
+        let vote = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), String::new()).expect("Vote to be created");
+        let _ = vote::create_vote(&conn, poll.clone(), &other_voter, VoteChoice::Option(poll.options[1].id), String::new());
+
+        assert_eq!(vote.voting_power, 3);
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].options[0].votes, 3);
+        assert_eq!(polls[0].options[1].votes, 1);
+
+        // Changing the weight later doesn't change the vote already cast
+        let _ = voter::set_voter_weight(&conn, "Ana".to_string(), 5);
+
+        let _ = vote::edit_vote(&conn, &vote, VoteChoice::Option(poll.options[1].id), String::new());
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].options[0].votes, 0);
+        assert_eq!(polls[0].options[1].votes, 4);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        let _ = vote::delete_vote(&conn, &vote);
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].options[1].votes, 1);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_abstain() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        assert_eq!(poll.find_choice("abstain"), Some(VoteChoice::Abstain));
+        assert_eq!(poll.find_choice("3"), Some(VoteChoice::Abstain));
+        assert_eq!(poll.find_choice("2"), Some(VoteChoice::Option(poll.options[1].id)));
+        assert_eq!(poll.find_choice("4"), None);
+
+        let voter = test_voter(&conn, "Ana");
+        let other_voter = test_voter(&conn, "Bruno");
+
+        let vote = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Abstain, String::new()).expect("Vote to be created");
+        let _ = vote::create_vote(&conn, poll.clone(), &other_voter, VoteChoice::Option(poll.options[0].id), String::new());
+
+        assert_eq!(vote.option_label, "Abstain");
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].abstentions, 1);
+        assert_eq!(polls[0].votes_cast(), 1);
+        assert_eq!(polls[0].turnout(), 2);
+
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes[0].choice, VoteChoice::Abstain);
+        assert_eq!(votes[0].option_label, "Abstain");
+
+        let _ = vote::edit_vote(&conn, &vote, VoteChoice::Option(poll.options[1].id), String::new());
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].abstentions, 0);
+        assert_eq!(polls[0].options[1].votes, 1);
+
+        let _ = vote::edit_vote(&conn, &vote, VoteChoice::Abstain, String::new());
+        let _ = vote::delete_vote(&conn, &vote);
+
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(polls[0].abstentions, 0);
+        assert_eq!(polls[0].options[1].votes, 0);
+        assert_eq!(polls[0].turnout(), 1);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_vote_option_from_other_poll() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+        let other_poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+        insert_poll(&conn, &other_poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(other_poll.options[0].id), "".to_string());
+
+        let expected_error = PollError::OptionNotInPoll { poll_id: poll.id, option_id: other_poll.options[0].id };
+
+        match vote_output {
+            Err(err) => {
+                assert_eq!(err, expected_error, "Different Errors");
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+        assert!(vote::get_votes(&conn)?.is_empty());
+        assert!(poll::get_polls(&conn)?.iter().all(|poll| poll.options.iter().all(|option| option.votes == 0)));
+
+        Ok(())
+    }
+    
+
+    #[test]
+    fn test_create_vote_twice_same_voter() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        assert!(vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string()).is_ok());
+        assert!(vote::has_voted(&conn, &poll.id, &voter.id)?);
+
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[1].id), "".to_string());
+
+        let expected_error = PollError::AlreadyVoted { poll_id: poll.id, voter_id: voter.id };
+
+        match vote_output {
+            Err(err) => {
+                assert_eq!(err, expected_error, "Different Errors");
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(polls[0].options[0].votes, 1);
+        assert_eq!(polls[0].options[1].votes, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unique_vote_per_voter_constraint() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let insert_vote = |option_id: Uuid| {
+            conn.execute(
+                "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id, voter_id) 
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                [
+                    &Uuid::new_v4().to_string(),
+                    &option_id.to_string(),
+                    "",
+                    "1",
+                    &Local::now().timestamp().to_string(),
+                    &poll.id.to_string(),
+                    &voter.id.to_string(),
+                ]
+            )
+        };
+
+        assert!(insert_vote(poll.options[0].id).is_ok());
+        assert!(insert_vote(poll.options[1].id).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_vote_on_closed_poll() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let mut poll = test_poll();
+        poll.create_date -= 24*60*60*31;
+        poll.start_date -= 24*60*60*31;
+        poll.expiration_date -= 24*60*60*31;
+
+        insert_poll(&conn, &poll)?;
+
+        assert!(poll.is_closed());
+
+        let voter = test_voter(&conn, "Ana");
+
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string());
+
+        match vote_output {
+            Err(err) => {
+                assert_eq!(err, PollError::PollClosed { poll_id: poll.id, expiration_date: poll.expiration_date });
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+        assert!(vote::get_votes(&conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_and_delete_vote_on_closed_poll() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let _ = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string());
+
+        // A poll struct read before the expiration is still rejected
+        conn.execute(
+            "UPDATE Poll SET expiration_date = ?1 WHERE id = ?2",
+            [(Local::now().timestamp() - 60).to_string(), poll.id.to_string()],
+        )?;
+
+        let votes = vote::get_votes(&conn)?;
+
+        let edit_output = vote::edit_vote(&conn, &votes[0], VoteChoice::Option(poll.options[1].id), "".to_string());
+        assert!(matches!(edit_output, Err(PollError::PollClosed { .. })));
+
+        let delete_output = vote::delete_vote(&conn, &votes[0]);
+        assert!(matches!(delete_output, Err(PollError::PollClosed { .. })));
+
+        let create_output = vote::create_vote(&conn, poll.clone(), &test_voter(&conn, "Bruno"), VoteChoice::Option(poll.options[0].id), "".to_string());
+        assert!(matches!(create_output, Err(PollError::PollClosed { .. })));
+
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+        assert_eq!(polls[0].options[0].votes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_vote_before_poll_starts() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let mut poll = test_poll();
+        poll.start_date += 24*60*60;
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string());
+
+        match vote_output {
+            Err(err) => {
+                assert_eq!(err, PollError::PollNotStarted { poll_id: poll.id, start_date: poll.start_date });
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+        assert!(vote::get_votes(&conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_vote_rolls_back_when_counter_fails() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail_counter BEFORE UPDATE ON PollOption BEGIN SELECT RAISE(ABORT, 'counter failure'); END;",
+        )?;
+
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string());
+
+        assert!(vote_output.is_err());
+        assert!(vote::get_votes(&conn)?.is_empty());
+        assert!(!vote::has_voted(&conn, &poll.id, &voter.id)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_and_delete_vote_roll_back_when_a_statement_fails() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+
+        let _ = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "".to_string());
+
+        let votes = vote::get_votes(&conn)?;
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail_vote_update BEFORE UPDATE ON Vote BEGIN SELECT RAISE(ABORT, 'vote failure'); END;",
+        )?;
+
+        // The counters are updated before the Vote row, which then fails
+        assert!(vote::edit_vote(&conn, &votes[0], VoteChoice::Option(poll.options[1].id), "".to_string()).is_err());
+
+        conn.execute_batch(
+            "CREATE TRIGGER fail_counter BEFORE UPDATE ON PollOption BEGIN SELECT RAISE(ABORT, 'counter failure'); END;",
+        )?;
+
+        // The Vote row is deleted before the counter, which then fails
+        assert!(vote::delete_vote(&conn, &votes[0]).is_err());
+
+        let votes = vote::get_votes(&conn)?;
+        let polls = poll::get_polls(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!(votes[0].choice, VoteChoice::Option(poll.options[0].id));
+        assert_eq!(polls[0].options[0].votes, 1);
+        assert_eq!(polls[0].options[1].votes, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vote_events() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = test_poll();
+
+        insert_poll(&conn, &poll)?;
+
+        let (yes, no) = (poll.options[0].id, poll.options[1].id);
+        let ana = test_voter(&conn, "Ana");
+        let bruno = test_voter(&conn, "Bruno");
+
+        let vote = vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(yes), "Sure".to_string()).expect("Vote to be created");
+        vote::create_vote(&conn, poll.clone(), &bruno, VoteChoice::Abstain, "".to_string()).expect("Vote to be created");
+        vote::edit_vote(&conn, &vote, VoteChoice::Option(no), "Changed my mind".to_string()).expect("Vote to be edited");
+        vote::delete_vote(&conn, &vote).expect("Vote to be deleted");
+
+        // Failed changes leave nothing in the log
+        assert!(vote::create_vote(&conn, poll.clone(), &bruno, VoteChoice::Option(yes), "".to_string()).is_err());
+
+        let events = vote::get_poll_events(&conn, &poll.id)?;
+        let actions: Vec<VoteAction> = events.iter().map(|event| event.action).collect();
+
+        assert_eq!(actions, vec![VoteAction::Cast, VoteAction::Cast, VoteAction::Changed, VoteAction::Retracted]);
+
+        assert_eq!((events[0].choice.clone(), events[0].previous_choice.clone()), (Some(VoteChoice::Option(yes)), None));
+        assert_eq!(events[1].option_label.as_deref(), Some("Abstain"));
+
+        assert_eq!(events[2].previous_choice, Some(VoteChoice::Option(yes)));
+        assert_eq!(events[2].choice, Some(VoteChoice::Option(no)));
+        assert_eq!((events[2].previous_label.as_deref(), events[2].option_label.as_deref()), (Some("Yes"), Some("No")));
+        assert_eq!((events[2].previous_comment.as_deref(), events[2].comment.as_deref()), (Some("Sure"), Some("Changed my mind")));
+
+        assert_eq!((events[3].previous_label.as_deref(), events[3].choice.clone()), (Some("No"), None));
+        assert!(events.iter().all(|event| event.poll_id == poll.id));
+
+        assert_eq!(vote::get_voter_events(&conn, &bruno.id)?.len(), 1);
+
+        // The log is kept after the poll is gone, with the votes that went with it retracted
+        poll::delete_poll_by_id(&conn, &poll.id).expect("Poll to be deleted");
+
+        let events = vote::get_poll_events(&conn, &poll.id)?;
+
+        assert_eq!(events.len(), 5);
+        assert_eq!((events[4].action, events[4].previous_label.as_deref()), (VoteAction::Retracted, Some("Abstain")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_anonymous_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let rules = PollRules { anonymous: true, ..Default::default() };
+        let poll = poll::create_poll(&conn, "Elect the board?".to_string(), "7".to_string(), vec!["Yes".to_string(), "No".to_string()], "".to_string(), rules, PollKind::Single, None).expect("Poll to be created");
+        let ana = voter::set_voter_weight(&conn, test_voter(&conn, "Ana").name, 3).expect("Weight to be set");
+
+        let vote = vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(poll.options[0].id), "".to_string()).expect("Vote to be created");
+
+        assert_eq!((vote.voter_id, vote.voter_name.as_str()), (None, vote::ANONYMOUS_NAME));
+
+        // Her weight would give her away if nobody else had it, so the vote counts once
+        assert_eq!(vote.voting_power, 1);
+
+        // The vote is counted and Ana can't vote twice, but nothing ties the vote back to her
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes.len(), 1);
+        assert_eq!((votes[0].voter_id, votes[0].voter_name.as_str()), (None, "Anonymous"));
+        assert_eq!(poll::get_poll(&conn, &poll.id)?.unwrap().options[0].votes, 1);
+        assert!(vote::has_voted(&conn, &poll.id, &ana.id)?);
+
+        let second_vote = vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(poll.options[1].id), "".to_string());
+        assert_eq!(second_vote.unwrap_err(), PollError::AlreadyVoted { poll_id: poll.id, voter_id: ana.id });
+
+        let events = vote::get_poll_events(&conn, &poll.id)?;
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].voter_id, None);
+        assert!(vote::get_voter_events(&conn, &ana.id)?.is_empty());
+
+        assert_eq!(vote::edit_vote(&conn, &vote, VoteChoice::Option(poll.options[1].id), "".to_string()).unwrap_err(), PollError::AnonymousVote(vote.id));
+        assert_eq!(vote::delete_vote(&conn, &vote).unwrap_err(), PollError::AnonymousVote(vote.id));
+
+        poll::delete_poll_by_id(&conn, &poll.id).expect("Poll to be deleted");
+
+        assert!(!vote::has_voted(&conn, &poll.id, &ana.id)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vote_receipts() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let rules = PollRules { anonymous: true, ..Default::default() };
+        let poll = poll::create_poll(&conn, "Elect the board?".to_string(), "7".to_string(), vec!["Yes".to_string(), "No".to_string()], "".to_string(), rules, PollKind::Single, None).expect("Poll to be created");
+        let (yes, no) = (poll.options[0].id, poll.options[1].id);
+        let ana = test_voter(&conn, "Ana");
+
+        let vote = vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(yes), "".to_string()).expect("Vote to be created");
+        let receipt = vote.receipt.clone().expect("A receipt");
+
+        assert_eq!(receipt.len(), 29);
+        assert!(vote::get_votes(&conn)?.iter().all(|vote| vote.receipt.is_none()));
+
+        // The receipt can be typed back without dashes or in upper case
+        let found = vote::get_vote_by_receipt(&conn, &receipt.replace('-', "").to_uppercase())?.expect("Vote to be found");
+        assert_eq!((found.id, found.choice), (vote.id, VoteChoice::Option(yes)));
+
+        let edited = vote::edit_vote_by_receipt(&conn, &receipt, VoteChoice::Option(no), "Changed my mind".to_string()).expect("Vote to be edited");
+        assert_eq!((edited.option_label.as_str(), edited.comment.as_str()), ("No", "Changed my mind"));
+
+        let counted = poll::get_poll(&conn, &poll.id)?.unwrap();
+        assert_eq!((counted.options[0].votes, counted.options[1].votes), (0, 1));
+
+        assert_eq!(vote::edit_vote_by_receipt(&conn, "0000-0000", VoteChoice::Option(yes), "".to_string()).unwrap_err(), PollError::ReceiptNotFound);
+
+        vote::delete_vote_by_receipt(&conn, &receipt).expect("Vote to be deleted");
+
+        assert!(vote::get_vote_by_receipt(&conn, &receipt)?.is_none());
+        assert_eq!(vote::delete_vote_by_receipt(&conn, &receipt).unwrap_err(), PollError::ReceiptNotFound);
+        assert_eq!(poll::get_poll(&conn, &poll.id)?.unwrap().options[1].votes, 0);
+
+        // Retracting takes back that Ana voted, so she can vote again
+        assert!(!vote::has_voted(&conn, &poll.id, &ana.id)?);
+
+        vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(yes), "".to_string()).expect("Vote to be created again");
+
+        assert_eq!(poll::get_poll(&conn, &poll.id)?.unwrap().options[0].votes, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_retracted_by_someone_else() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let rules = PollRules { anonymous: true, ..Default::default() };
+        let poll = poll::create_poll(&conn, "Elect the board?".to_string(), "7".to_string(), vec!["Yes".to_string(), "No".to_string()], "".to_string(), rules, PollKind::Single, None).expect("Poll to be created");
+        let yes = poll.options[0].id;
+        let (ana, bruno) = (test_voter(&conn, "Ana"), test_voter(&conn, "Bruno"));
+
+        let receipt = vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(yes), "".to_string()).expect("Vote to be created").receipt.expect("A receipt");
+        vote::create_vote(&conn, poll.clone(), &bruno, VoteChoice::Option(yes), "".to_string()).expect("Vote to be created");
+
+        // Bruno retracts Ana's vote with her receipt. It is Ana who can vote again, not Bruno
+        vote::delete_vote_by_receipt(&conn, &receipt).expect("Vote to be deleted");
+
+        assert!(!vote::has_voted(&conn, &poll.id, &ana.id)?);
+        assert!(vote::has_voted(&conn, &poll.id, &bruno.id)?);
+        assert_eq!(vote::create_vote(&conn, poll.clone(), &bruno, VoteChoice::Option(yes), "".to_string()).unwrap_err(), PollError::AlreadyVoted { poll_id: poll.id, voter_id: bruno.id });
+
+        vote::create_vote(&conn, poll.clone(), &ana, VoteChoice::Option(yes), "".to_string()).expect("Vote to be created again");
+
+        assert_eq!(poll::get_poll(&conn, &poll.id)?.unwrap().options[0].votes, 2);
+
+        Ok(())
+    }
+
+    #[test]
+
+    fn test_comment_to_long() -> Result<()>{
+        let conn = Connection::open_in_memory()?;
+        
+        create_tables(&conn)?;
+    
+        let poll = test_poll();
+        
+        insert_poll(&conn, &poll)?;
+
+        let voter = test_voter(&conn, "Ana");
+    
+        let polls = poll::get_polls(&conn)?;
+        
+        assert_eq!(polls[0].options[0].votes, 0);
+        
+        let vote_output = vote::create_vote(&conn, poll.clone(), &voter, VoteChoice::Option(poll.options[0].id), "test".repeat(151));
+    
+        let expected_error = PollError::CommentTooLong { max: 100 };
+
+        match vote_output {
+            Err(err) => {
+                println!("Output error: {:?}", err);
+
+                assert_eq!(err, expected_error, "Different Errors");
+                Ok(())
+            }
+            Ok(_) => panic!("Expected Error."),
+        }
+
+    }
+}
+
+#[cfg(test)]
+mod voters {
+    use rusqlite::{Connection, Result};
+
+    use crate::create_tables;
+
+    use crate::voter;
+    use crate::error::PollError;
+
+    #[test]
+    fn test_create_voter() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let voter_output = voter::create_voter(&conn, " Ana ".to_string());
+
+        match voter_output {
+            Ok(created_voter) => {
+                assert_eq!(created_voter.name, "Ana");
+                assert_eq!(voter::find_voter(&conn, "Ana")?, Some(created_voter));
+            }
+            Err(e) => panic!("Error: {}", e),
+        }
+
+        assert_eq!(voter::find_voter(&conn, "Bruno")?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_create_voter_invalid_name() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let _ = voter::create_voter(&conn, "Ana".to_string());
+
+        let cases = [
+            ("  ".to_string(), PollError::VoterNameEmpty),
+            ("A".repeat(51), PollError::VoterNameTooLong { max: 50 }),
+            ("Ana".to_string(), PollError::VoterNameTaken),
+        ];
+
+        for (name, expected_error) in cases {
+            match voter::create_voter(&conn, name) {
+                Err(err) => {
+                    assert_eq!(err, expected_error, "Different Errors");
+                }
+                Ok(_) => panic!("Expected Error."),
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_weight_of_unknown_voter() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        // Setting a weight doesn't make up a voter, which would keep whoever has the name from signing up
+        assert_eq!(voter::set_voter_weight(&conn, "Ana".to_string(), 2), Err(PollError::VoterNotFound));
+        assert!(voter::get_voters(&conn)?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_set_voter_weight() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let created = voter::create_voter(&conn, "Ana".to_string()).expect("Voter to be created");
+
+        assert_eq!(created.weight, 1);
+
+        let weighted = voter::set_voter_weight(&conn, "Ana".to_string(), 2).expect("Weight to be set");
+
+        assert_eq!(weighted.id, created.id);
+        assert_eq!(voter::find_voter(&conn, "Ana")?, Some(weighted));
+
+        for weight in [0, -1] {
+            assert_eq!(voter::set_voter_weight(&conn, "Ana".to_string(), weight), Err(PollError::InvalidWeight));
+        }
+
+        let voters = voter::get_voters(&conn)?;
+
+        assert_eq!(voters.len(), 1);
+        assert_eq!(voters[0].weight, 2);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod users {
+    use rusqlite::{Connection, Result};
+
+    use crate::create_tables;
+
+    use crate::user;
+    use crate::user::Role;
+    use crate::voter;
+    use crate::vote;
+    use crate::vote::VoteChoice;
+    use crate::poll;
+    use crate::poll::{PollKind, PollRules};
+    use crate::permission::{self, Action};
+    use crate::error::PollError;
+    use super::helpers::yes_no;
+
+    #[test]
+    fn test_create_user_and_login() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        assert_eq!(user::create_user(&conn, "Ana".to_string(), "short").unwrap_err(), PollError::PasswordTooShort { min: 8 });
+        assert_eq!(user::create_user(&conn, " ".to_string(), "correct horse").unwrap_err(), PollError::UserNameEmpty);
+
+        let ana = user::create_user(&conn, " Ana ".to_string(), "correct horse").expect("User to be created");
+
+        assert_eq!(ana.name, "Ana");
+        assert_eq!(user::user_voter(&conn, &ana)?.name, "Ana");
+        assert_eq!(user::create_user(&conn, "Ana".to_string(), "battery staple").unwrap_err(), PollError::UserNameTaken);
+
+        // The password is only kept as an argon2 hash
+        let password_hash: String = conn.query_row("SELECT password_hash FROM User", [], |row| row.get(0))?;
+        assert!(password_hash.starts_with("$argon2id$"));
+
+        assert_eq!(user::login(&conn, "Ana", "correct horse").expect("Login to work"), ana);
+        assert_eq!(user::login(&conn, "Ana", "wrong horse").unwrap_err(), PollError::InvalidLogin);
+        assert_eq!(user::login(&conn, "Bruno", "correct horse").unwrap_err(), PollError::InvalidLogin);
+
+        let token = user::create_session(&conn, &ana)?;
+
+        assert_eq!(user::session_user(&conn, &token)?, Some(ana));
+        assert_eq!(user::session_user(&conn, "not-a-token")?, None);
+
+        user::end_session(&conn, &token)?;
+
+        assert_eq!(user::session_user(&conn, &token)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invited_voter_signs_up() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        user::create_user(&conn, "Ana".to_string(), "correct horse").expect("User to be created");
+
+        // Carla voted before there were accounts. Nobody can sign up under her name and take her votes
+        let carla = voter::create_voter(&conn, "Carla".to_string()).expect("Voter to be created");
+        let carla = voter::set_voter_weight(&conn, carla.name, 3).expect("Weight to be set");
+
+        assert_eq!(user::create_user(&conn, " Carla ".to_string(), "correct horse").unwrap_err(), PollError::UserNameTaken);
+        assert_eq!(user::invite_voter(&conn, "Bruno").unwrap_err(), PollError::VoterNotFound);
+        assert_eq!(user::invite_voter(&conn, "Ana").unwrap_err(), PollError::VoterHasUser);
+
+        // An admin invites her, and she signs up as the voter she already is
+        let code = user::invite_voter(&conn, "Carla").expect("Invite to be made");
+
+        assert_eq!(user::join(&conn, "not-an-invite", "correct horse").unwrap_err(), PollError::InvalidInvite);
+        assert_eq!(user::join(&conn, &code, "short").unwrap_err(), PollError::PasswordTooShort { min: 8 });
+
+        let user = user::join(&conn, &code, "correct horse").expect("User to be created");
+
+        assert_eq!((user.name.as_str(), user.voter_id, user.role), ("Carla", carla.id, Role::Voter));
+        assert_eq!(user::user_voter(&conn, &user)?.weight, 3);
+        assert_eq!(user::login(&conn, "Carla", "correct horse").expect("Login to work"), user);
+
+        // The invite is used up
+        assert_eq!(user::join(&conn, &code, "correct horse").unwrap_err(), PollError::InvalidInvite);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_roles_and_permissions() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        // The first account is the admin, everyone after starts as a voter
+        let ana = user::create_user(&conn, "Ana".to_string(), "correct horse").expect("User to be created");
+        let bruno = user::create_user(&conn, "Bruno".to_string(), "correct horse").expect("User to be created");
+
+        assert_eq!(ana.role, Role::Admin);
+        assert_eq!(bruno.role, Role::Voter);
+        assert_eq!(permission::check(&bruno, Action::CreatePoll).unwrap_err(), PollError::PermissionDenied(Action::CreatePoll));
+
+        assert_eq!(user::set_user_role(&conn, "Carla", Role::Observer).unwrap_err(), PollError::UserNotFound);
+        assert_eq!(user::set_user_role(&conn, "Ana", Role::Voter).unwrap_err(), PollError::LastAdmin);
+        assert_eq!("poll manager".parse::<Role>(), Ok(Role::PollManager));
+        assert_eq!("owner".parse::<Role>(), Err(PollError::InvalidRole));
+
+        let bruno = user::set_user_role(&conn, "Bruno", Role::PollManager).expect("Role to be set");
+        let carla = user::create_user(&conn, "Carla".to_string(), "correct horse").expect("User to be created");
+
+        assert_eq!(user::find_user(&conn, "Bruno")?.map(|user| user.role), Some(Role::PollManager));
+
+        // Poll managers only manage their own polls, admins manage all of them
+        let poll = poll::create_poll(&conn, "Lunch?".to_string(), "3d".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, Some(&bruno))
+            .expect("Poll to be created");
+
+        assert_eq!(permission::check_poll(&bruno, &poll), Ok(()));
+        assert_eq!(permission::check_poll(&ana, &poll), Ok(()));
+        assert_eq!(permission::check_poll(&carla, &poll).unwrap_err(), PollError::PermissionDenied(Action::ManagePoll));
+
+        // Only whoever cast a vote can change it
+        let vote = vote::create_vote(&conn, poll.clone(), &user::user_voter(&conn, &carla)?, VoteChoice::Option(poll.options[0].id), String::new())
+            .expect("Vote to be created");
+
+        assert_eq!(permission::check_vote(&carla, &vote), Ok(()));
+        assert_eq!(permission::check_vote(&bruno, &vote).unwrap_err(), PollError::PermissionDenied(Action::ChangeVote));
+
+        let carla = user::set_user_role(&conn, "Carla", Role::Observer).expect("Role to be set");
+
+        assert_eq!(permission::check(&carla, Action::CastVote).unwrap_err(), PollError::PermissionDenied(Action::CastVote));
+        assert_eq!(permission::check_vote(&carla, &vote).unwrap_err(), PollError::PermissionDenied(Action::ChangeVote));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tally {
+    use rusqlite::{Connection, Result};
+    use uuid::Uuid;
+
+    use crate::create_tables;
+    use crate::error::PollError;
+    use crate::poll;
+    use crate::poll::{PollKind, PollOption, PollRules};
+    use crate::tally::{instant_runoff, Ballot};
+    use crate::vote;
+    use crate::vote::VoteChoice;
+    use crate::voter;
+    use super::helpers::{test_voter, yes_no};
+
+    fn options(labels: &[&str]) -> Vec<PollOption> {
+        let poll_id = Uuid::new_v4();
+
+        labels
+            .iter()
+            .map(|label| PollOption { id: Uuid::new_v4(), poll_id, label: label.to_string(), votes: 0 })
+            .collect()
+    }
+
+    #[test]
+    fn test_instant_runoff() {
+        let options = options(&["Tea", "Coffee", "Juice"]);
+        let (tea, coffee, juice) = (options[0].id, options[1].id, options[2].id);
+
+        let ballots = vec![
+            Ballot { ranking: vec![tea], weight: 4 },
+            Ballot { ranking: vec![coffee, tea], weight: 3 },
+            Ballot { ranking: vec![juice, coffee], weight: 2 },
+        ];
+
+        let rounds = instant_runoff(&options, &ballots);
+
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].tallies.iter().map(|tally| tally.votes).collect::<Vec<i64>>(), vec![4, 3, 2]);
+        assert_eq!(rounds[0].eliminated, vec![juice]);
+        assert_eq!(rounds[0].majority(), None);
+        assert_eq!(rounds[1].tallies.iter().map(|tally| tally.votes).collect::<Vec<i64>>(), vec![4, 5]);
+        assert_eq!(rounds[1].majority().map(|tally| tally.option_id), Some(coffee));
+
+        // Once its options are out a ballot is exhausted
+        let rounds = instant_runoff(&options, &[
+            Ballot { ranking: vec![tea], weight: 2 },
+            Ballot { ranking: vec![coffee], weight: 2 },
+            Ballot { ranking: vec![juice], weight: 1 },
+        ]);
+
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[1].exhausted, 1);
+        assert!(rounds[1].eliminated.is_empty());
+        assert_eq!(rounds[1].majority(), None);
+
+        assert!(instant_runoff(&options, &[]).is_empty());
+    }
+
+    #[test]
+    fn test_instant_runoff_ties() {
+        let drinks = options(&["Tea", "Coffee", "Juice"]);
+        let (tea, coffee, juice) = (drinks[0].id, drinks[1].id, drinks[2].id);
+
+        // Only one of the options tied at the bottom goes, the one listed last
+        let rounds = instant_runoff(&drinks, &[
+            Ballot { ranking: vec![tea], weight: 2 },
+            Ballot { ranking: vec![coffee, tea], weight: 1 },
+            Ballot { ranking: vec![juice, coffee], weight: 1 },
+        ]);
+
+        assert_eq!(rounds.len(), 2);
+        assert_eq!(rounds[0].eliminated, vec![juice]);
+        assert_eq!(rounds[1].tallies.iter().map(|tally| tally.votes).collect::<Vec<i64>>(), vec![2, 2]);
+        assert!(rounds[1].eliminated.is_empty());
+
+        // Unless one of them had fewer votes the round before
+        let options = options(&["Tea", "Juice", "Coffee", "Water"]);
+        let (tea, juice, coffee, water) = (options[0].id, options[1].id, options[2].id, options[3].id);
+
+        let rounds = instant_runoff(&options, &[
+            Ballot { ranking: vec![tea], weight: 6 },
+            Ballot { ranking: vec![coffee], weight: 4 },
+            Ballot { ranking: vec![juice], weight: 3 },
+            Ballot { ranking: vec![water, juice], weight: 1 },
+        ]);
+
+        assert_eq!(rounds.len(), 3);
+        assert_eq!(rounds[0].eliminated, vec![water]);
+        assert_eq!(rounds[1].tallies.iter().map(|tally| tally.votes).collect::<Vec<i64>>(), vec![6, 4, 4]);
+        assert_eq!(rounds[1].eliminated, vec![juice]);
+        assert_eq!(rounds[2].majority().map(|tally| tally.option_id), Some(tea));
+    }
+
+    #[test]
+    fn test_ranked_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let labels = vec!["Tea".to_string(), "Coffee".to_string(), "Juice".to_string()];
+        let ranked_poll = poll::create_poll(&conn, "What to drink?".to_string(), "7".to_string(), labels, "".to_string(), PollRules::default(), PollKind::Ranked, None)
+            .expect("Poll to be created");
+        let (tea, coffee, juice) = (ranked_poll.options[0].id, ranked_poll.options[1].id, ranked_poll.options[2].id);
+
+        assert_eq!(ranked_poll.find_choice("2, 1"), Some(VoteChoice::Ranked(vec![coffee, tea])));
+        assert_eq!(ranked_poll.find_choice("Juice > Coffee"), Some(VoteChoice::Ranked(vec![juice, coffee])));
+        assert_eq!(ranked_poll.find_choice("tea"), Some(VoteChoice::Ranked(vec![tea])));
+        assert_eq!(ranked_poll.find_choice("2,5"), None);
+
+        let rankings = [
+            ("Ana", vec![tea]),
+            ("Bruno", vec![tea, coffee]),
+            ("Carla", vec![coffee, tea]),
+            ("Davi", vec![juice, coffee]),
+            ("Eva", vec![juice, coffee]),
+        ];
+
+        for (name, ranking) in rankings {
+            vote::create_vote(&conn, ranked_poll.clone(), &test_voter(&conn, name), VoteChoice::Ranked(ranking), String::new())
+                .expect("Vote to be created");
+        }
+
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes[1].choice, VoteChoice::Ranked(vec![tea, coffee]));
+        assert_eq!(votes[1].option_label, "Tea > Coffee");
+
+        // First preferences are the option counters, so they can still be verified
+        let loaded = poll::get_poll(&conn, &ranked_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![2, 1, 2]);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        assert_eq!(loaded.runoff.len(), 2);
+        assert_eq!(loaded.runoff[0].eliminated, vec![coffee]);
+        assert_eq!(loaded.runoff[1].tallies.iter().map(|tally| tally.votes).collect::<Vec<i64>>(), vec![3, 2]);
+        assert_eq!(loaded.leading_option().map(|option| option.id), Some(tea));
+
+        // Changing the ranking moves the first preference and the runoff follows
+        vote::edit_vote(&conn, &votes[1], VoteChoice::Ranked(vec![juice, tea]), String::new()).expect("Vote to be edited");
+        vote::delete_vote(&conn, &votes[0]).expect("Vote to be deleted");
+
+        let loaded = poll::get_poll(&conn, &ranked_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![0, 1, 3]);
+        assert_eq!(loaded.leading_option().map(|option| option.id), Some(juice));
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        let voter = test_voter(&conn, "Fabio");
+
+        assert_eq!(
+            vote::create_vote(&conn, ranked_poll.clone(), &voter, VoteChoice::Ranked(vec![tea, tea]), String::new()).err(),
+            Some(PollError::InvalidRanking)
+        );
+
+        let single_poll = poll::create_poll(&conn, "Tea?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        assert_eq!(
+            vote::create_vote(&conn, single_poll.clone(), &voter, VoteChoice::Ranked(vec![single_poll.options[0].id, single_poll.options[1].id]), String::new()).err(),
+            Some(PollError::BallotNotAllowed(PollKind::Single))
+        );
+
+        poll::delete_poll_by_id(&conn, &ranked_poll.id).expect("Poll to be deleted");
+
+        let entries: i64 = conn.query_row("SELECT COUNT(*) FROM BallotEntry", [], |row| row.get(0))?;
+
+        assert_eq!(entries, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_approval_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let labels = vec!["Tea".to_string(), "Coffee".to_string(), "Juice".to_string()];
+        let approval_poll = poll::create_poll(&conn, "What to drink?".to_string(), "7".to_string(), labels, "".to_string(), PollRules::default(), PollKind::Approval, None)
+            .expect("Poll to be created");
+        let (tea, coffee, juice) = (approval_poll.options[0].id, approval_poll.options[1].id, approval_poll.options[2].id);
+
+        assert_eq!(approval_poll.find_choice("1, Juice"), Some(VoteChoice::Approved(vec![tea, juice])));
+        assert_eq!(approval_poll.find_choice("coffee"), Some(VoteChoice::Approved(vec![coffee])));
+        assert_eq!(approval_poll.find_choice("2 > 1"), None);
+
+        let ballots = [
+            ("Ana", vec![tea, coffee]),
+            ("Bruno", vec![coffee]),
+            ("Carla", vec![coffee, juice]),
+        ];
+
+        for (name, approved) in ballots {
+            vote::create_vote(&conn, approval_poll.clone(), &test_voter(&conn, name), VoteChoice::Approved(approved), String::new())
+                .expect("Vote to be created");
+        }
+
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes[0].choice, VoteChoice::Approved(vec![tea, coffee]));
+        assert_eq!(votes[0].option_label, "Tea, Coffee");
+
+        // Each approved option gets the vote, while the votes cast are the ballots
+        let loaded = poll::get_poll(&conn, &approval_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![1, 3, 1]);
+        assert_eq!(loaded.votes_cast(), 3);
+        assert_eq!(loaded.leading_option().map(|option| option.id), Some(coffee));
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        vote::edit_vote(&conn, &votes[1], VoteChoice::Approved(vec![tea, juice]), String::new()).expect("Vote to be edited");
+        vote::delete_vote(&conn, &votes[2]).expect("Vote to be deleted");
+
+        let loaded = poll::get_poll(&conn, &approval_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![2, 1, 1]);
+        assert_eq!(loaded.votes_cast(), 2);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        let voter = test_voter(&conn, "Davi");
+
+        assert_eq!(
+            vote::create_vote(&conn, approval_poll.clone(), &voter, VoteChoice::Approved(Vec::new()), String::new()).err(),
+            Some(PollError::InvalidApproval)
+        );
+        assert_eq!(
+            vote::create_vote(&conn, approval_poll.clone(), &voter, VoteChoice::Ranked(vec![tea, coffee]), String::new()).err(),
+            Some(PollError::BallotNotAllowed(PollKind::Approval))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_score_votes() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let labels = vec!["Tea".to_string(), "Coffee".to_string()];
+        let score_poll = poll::create_poll(&conn, "What to drink?".to_string(), "7".to_string(), labels, "".to_string(), PollRules::default(), PollKind::Score, None)
+            .expect("Poll to be created");
+        let (tea, coffee) = (score_poll.options[0].id, score_poll.options[1].id);
+
+        assert_eq!(score_poll.find_choice("5, 2"), Some(VoteChoice::Scored(vec![(tea, 5), (coffee, 2)])));
+        assert_eq!(score_poll.find_choice("tea"), None);
+        assert_eq!(score_poll.find_choice("3"), Some(VoteChoice::Abstain));
+
+        let heavy_voter = voter::set_voter_weight(&conn, test_voter(&conn, "Ana").name, 2).expect("Weight to be set");
+
+        vote::create_vote(&conn, score_poll.clone(), &heavy_voter, VoteChoice::Scored(vec![(tea, 4), (coffee, 1)]), String::new())
+            .expect("Vote to be created");
+        vote::create_vote(&conn, score_poll.clone(), &test_voter(&conn, "Bruno"), VoteChoice::Scored(vec![(coffee, 5), (tea, 0)]), String::new())
+            .expect("Vote to be created");
+
+        let votes = vote::get_votes(&conn)?;
+
+        assert_eq!(votes[1].choice, VoteChoice::Scored(vec![(coffee, 5), (tea, 0)]));
+        assert_eq!(votes[1].option_label, "Coffee 5, Tea 0");
+
+        // Points are the scores times the voting power, averaged over the voting power of the ballots
+        let loaded = poll::get_poll(&conn, &score_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![8, 7]);
+        assert_eq!(loaded.average_score(&loaded.options[0]), Some(8.0 / 3.0));
+        assert_eq!(loaded.leading_option().map(|option| option.id), Some(tea));
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        vote::edit_vote(&conn, &votes[0], VoteChoice::Scored(vec![(tea, 2), (coffee, 3)]), String::new()).expect("Vote to be edited");
+
+        let loaded = poll::get_poll(&conn, &score_poll.id)?.expect("Poll to be found");
+
+        assert_eq!(loaded.options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![4, 11]);
+        assert!(poll::verify_tallies(&conn, false)?.is_empty());
+
+        let voter = test_voter(&conn, "Carla");
+
+        for scores in [vec![(tea, 6), (coffee, 1)], vec![(tea, 3)], vec![(tea, 3), (tea, 3)]] {
+            assert_eq!(
+                vote::create_vote(&conn, score_poll.clone(), &voter, VoteChoice::Scored(scores), String::new()).err(),
+                Some(PollError::InvalidScores { max: 5 })
+            );
+        }
+
+        assert_eq!(
+            vote::create_vote(&conn, score_poll.clone(), &voter, VoteChoice::Option(tea), String::new()).err(),
+            Some(PollError::BallotNotAllowed(PollKind::Score))
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod ledger {
+    use rusqlite::{Connection, Result};
+    use uuid::Uuid;
+
+    use crate::create_tables;
+    use crate::ledger::{event_hash, has_head, verify_ledger, LedgerIssue, GENESIS_HASH};
+    use crate::poll;
+    use crate::poll::{Poll, PollKind, PollRules};
+    use crate::vote;
+    use crate::vote::{VoteAction, VoteChoice};
+    use super::helpers::{test_voter, yes_no};
+
+    // Two votes cast, the first one changed and the second one retracted
+    fn logged_votes() -> Result<(Connection, Poll)> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let poll = poll::create_poll(&conn, "Lunch?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), PollKind::Single, None)
+            .expect("Poll to be created");
+
+        let first = vote::create_vote(&conn, poll.clone(), &test_voter(&conn, "Ana"), VoteChoice::Option(poll.options[0].id), "".to_string())
+            .expect("Vote to be created");
+        let second = vote::create_vote(&conn, poll.clone(), &test_voter(&conn, "Bruno"), VoteChoice::Abstain, "".to_string())
+            .expect("Vote to be created");
+
+        vote::edit_vote(&conn, &first, VoteChoice::Option(poll.options[1].id), "Changed".to_string()).expect("Vote to be edited");
+        vote::delete_vote(&conn, &second).expect("Vote to be deleted");
+
+        Ok((conn, poll))
+    }
+
+    fn issue(conn: &Connection) -> Result<Option<(Option<usize>, LedgerIssue)>> {
+        Ok(verify_ledger(conn)?.problem.map(|problem| (problem.entry, problem.issue)))
+    }
+
+    #[test]
+    fn test_intact_ledger() -> Result<()> {
+        let (conn, poll) = logged_votes()?;
+
+        let events = vote::get_events(&conn)?;
+
+        assert_eq!(events[0].previous_hash, GENESIS_HASH);
+        assert!(events.windows(2).all(|pair| pair[1].previous_hash == pair[0].hash));
+        assert_eq!(verify_ledger(&conn)?.entries, 4);
+        assert_eq!(issue(&conn)?, None);
+
+        // Deleting a poll takes its votes, which the log records as retracted
+        poll::delete_poll_by_id(&conn, &poll.id).expect("Poll to be deleted");
+
+        let events = vote::get_events(&conn)?;
+
+        assert_eq!(events.len(), 5);
+        assert_eq!(events[4].action, VoteAction::Retracted);
+        assert_eq!(issue(&conn)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ballots_of_one_option() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        for kind in [PollKind::Ranked, PollKind::Approval] {
+            let poll = poll::create_poll(&conn, "Lunch?".to_string(), "7".to_string(), yes_no(), "".to_string(), PollRules::default(), kind, None)
+                .expect("Poll to be created");
+            let choice = poll.find_choice("1").expect("A choice");
+
+            vote::create_vote(&conn, poll, &test_voter(&conn, &kind.to_string()), choice, "".to_string()).expect("Vote to be created");
+        }
+
+        assert_eq!(issue(&conn)?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tampered_ledger() -> Result<()> {
+        let (conn, _) = logged_votes()?;
+        conn.execute("UPDATE VoteEvent SET comment = 'Forged' WHERE rowid = 3", ())?;
+        assert_eq!(issue(&conn)?, Some((Some(3), LedgerIssue::HashMismatch)));
+
+        let (conn, _) = logged_votes()?;
+        conn.execute("DELETE FROM VoteEvent WHERE rowid = 2", ())?;
+        assert_eq!(issue(&conn)?, Some((Some(2), LedgerIssue::BrokenChain)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rehashed_ledger() -> Result<()> {
+        let (conn, _) = logged_votes()?;
+        let head = verify_ledger(&conn)?.head;
+
+        assert_eq!(head, vote::get_events(&conn)?[3].hash);
+        assert!(has_head(&conn, &head)?);
+
+        // Changing an entry and writing the chain again from it passes the check on its own,
+        // but the head kept from before is gone
+        conn.execute("UPDATE VoteEvent SET comment = 'Forged' WHERE rowid = 3", ())?;
+        conn.execute("UPDATE Vote SET comment = 'Forged'", ())?;
+
+        let mut previous_hash = GENESIS_HASH.to_string();
+
+        for mut event in vote::get_events(&conn)? {
+            event.previous_hash = previous_hash;
+            event.hash = event_hash(&event);
+            conn.execute("UPDATE VoteEvent SET previous_hash = ?1, hash = ?2 WHERE id = ?3", (&event.previous_hash, &event.hash, event.id.to_string()))?;
+            previous_hash = event.hash;
+        }
+
+        assert_eq!(issue(&conn)?, None);
+        assert!(!has_head(&conn, &head)?);
+        assert!(has_head(&conn, GENESIS_HASH)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_votes_against_ledger() -> Result<()> {
+        let (conn, _) = logged_votes()?;
+        conn.execute("UPDATE Vote SET comment = 'Forged'", ())?;
+        assert_eq!(issue(&conn)?, Some((Some(3), LedgerIssue::VoteDiffers)));
+
+        let (conn, _) = logged_votes()?;
+        conn.execute("DELETE FROM Vote", ())?;
+        assert_eq!(issue(&conn)?, Some((Some(3), LedgerIssue::VoteMissing)));
+
+        // Removing the poll by hand along with its votes doesn't hide them
+        let (conn, _) = logged_votes()?;
+        conn.execute_batch("DELETE FROM Vote; DELETE FROM PollOption; DELETE FROM Poll;")?;
+        assert_eq!(issue(&conn)?, Some((Some(3), LedgerIssue::VoteMissing)));
+
+        let (conn, poll) = logged_votes()?;
+        let voter = test_voter(&conn, "Carla");
+        conn.execute(
+            "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id, voter_id) VALUES (?1, ?2, '', 1, 0, ?3, ?4)",
+            (Uuid::new_v4().to_string(), poll.options[0].id.to_string(), poll.id.to_string(), voter.id.to_string()),
+        )?;
+        assert_eq!(issue(&conn)?, Some((None, LedgerIssue::VoteNotLogged)));
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod server {
+    use rusqlite::{Connection, Result};
+    use uuid::Uuid;
+    use chrono::Local;
+
+    use crate::create_tables;
+    use crate::poll::{Poll, PollDuration, PollKind, PollRules, PollStatus};
+    use crate::server::handle;
+    use crate::user;
+    use crate::voter;
+    use super::helpers::{insert_poll, yes_no_options};
+
+    // Creates an account and logs in with it, returning the token. The first account is made like from the
+    // command line, since the server only takes sign-ups once there is an admin
+    fn login(conn: &Connection, name: &str) -> String {
+        let credentials = format!(r#"{{"name": "{}", "password": "correct horse"}}"#, name);
+
+        if user::get_users(conn).unwrap().is_empty() {
+            user::create_user(conn, name.to_string(), "correct horse").expect("User to be created");
+        } else {
+            assert_eq!(handle(conn, "POST", "/users", None, &credentials).status, 201);
+        }
+
+        let session = handle(conn, "POST", "/sessions", None, &credentials);
+        assert_eq!(session.status, 201);
+
+        session.body["token"].as_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_create_vote_edit_and_delete() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let token = login(&conn, "Ana");
+
+        let created = handle(&conn, "POST", "/polls", Some(&token), r#"{"question": "Lunch?", "duration": "3d", "options": ["Pizza", "Sushi"]}"#);
+        assert_eq!(created.status, 201);
+        assert_eq!(created.body["options"][1]["label"], "Sushi");
+
+        let poll_id = created.body["id"].as_str().unwrap().to_string();
+
+        let vote = handle(&conn, "POST", &format!("/polls/{}/votes", poll_id), Some(&token), r#"{"choice": "pizza"}"#);
+        assert_eq!(vote.status, 201);
+        assert_eq!(vote.body["option_label"], "Pizza");
+
+        let vote_id = vote.body["id"].as_str().unwrap().to_string();
+
+        let edited = handle(&conn, "PATCH", &format!("/votes/{}", vote_id), Some(&token), r#"{"choice": "2", "comment": "Changed my mind"}"#);
+        assert_eq!(edited.status, 200);
+        assert_eq!(edited.body["option_label"], "Sushi");
+        assert_eq!(edited.body["comment"], "Changed my mind");
+
+        let results = handle(&conn, "GET", &format!("/polls/{}/results", poll_id), Some(&token), "");
+        assert_eq!(results.status, 200);
+        assert_eq!(results.body["options"][0]["votes"], 0);
+        assert_eq!(results.body["options"][1]["votes"], 1);
+
+        let receipt = vote.body["receipt"].as_str().unwrap().to_string();
+
+        let checked = handle(&conn, "GET", &format!("/receipts/{}", receipt), Some(&token), "");
+        assert_eq!(checked.status, 200);
+        assert_eq!(checked.body["option_label"], "Sushi");
+        assert!(checked.body.get("receipt").is_none());
+
+        assert_eq!(handle(&conn, "PATCH", &format!("/receipts/{}", receipt), Some(&token), r#"{"choice": "1"}"#).body["option_label"], "Pizza");
+        assert_eq!(handle(&conn, "DELETE", &format!("/receipts/{}", receipt), Some(&token), "").status, 204);
+        assert_eq!(handle(&conn, "GET", &format!("/receipts/{}", receipt), Some(&token), "").status, 404);
+
+        assert_eq!(handle(&conn, "DELETE", &format!("/polls/{}", poll_id), Some(&token), "").status, 204);
+        assert_eq!(handle(&conn, "GET", "/polls", Some(&token), "").body, serde_json::json!([]));
+        assert_eq!(handle(&conn, "GET", &format!("/polls/{}/results", poll_id), Some(&token), "").status, 404);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors_are_structured() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        // Nobody signs up over the API before the admin was made locally
+        let no_admin = handle(&conn, "POST", "/users", None, r#"{"name": "Ana", "password": "correct horse"}"#);
+        assert_eq!(no_admin.status, 403);
+        assert_eq!(no_admin.body["error"], "no_admin");
+
+        let token = login(&conn, "Ana");
+
+        let anonymous = handle(&conn, "POST", "/polls", None, r#"{"question": "Lunch?", "duration": "3d"}"#);
+        assert_eq!(anonymous.status, 401);
+        assert_eq!(anonymous.body["error"], "login_required");
+        assert_eq!(handle(&conn, "POST", "/polls", Some("not-a-token"), r#"{"question": "Lunch?", "duration": "3d"}"#).status, 401);
+        assert_eq!(handle(&conn, "POST", "/sessions", None, r#"{"name": "Ana", "password": "wrong password"}"#).body["error"], "invalid_login");
+
+        let invalid = handle(&conn, "POST", "/polls", Some(&token), r#"{"question": "Lunch?", "duration": "5x"}"#);
+        assert_eq!(invalid.status, 400);
+        assert_eq!(invalid.body["error"], "validation");
+        assert_eq!(invalid.body["message"], "Invalid input for Poll Duration. Use hours (36h), days (3d) or an end date (2024-12-31).");
+
+        assert_eq!(handle(&conn, "POST", "/polls", Some(&token), "not json").body["error"], "invalid_body");
+        let unknown_poll = handle(&conn, "GET", "/polls/not-a-poll/results", Some(&token), "");
+        assert_eq!(unknown_poll.status, 404);
+        assert_eq!(unknown_poll.body["message"], "Poll not found.");
+        assert_eq!(handle(&conn, "PATCH", &format!("/votes/{}", Uuid::new_v4()), Some(&token), r#"{"choice": "Yes"}"#).status, 404);
+        assert_eq!(handle(&conn, "PUT", "/polls", Some(&token), "").status, 405);
+        assert_eq!(handle(&conn, "GET", "/voters", Some(&token), "").status, 404);
+
+        let poll_id = Uuid::new_v4();
+        let now = Local::now().timestamp();
+
+        let closed_poll = Poll {
+            id: poll_id,
+            question: "Closed?".to_string(),
+            poll_duration: PollDuration::Days(7),
+            create_date: now - 8*24*60*60,
+            start_date: now - 8*24*60*60,
+            expiration_date: now - 24*60*60,
+            options: yes_no_options(poll_id),
+            abstentions: 0,
+            ballots: 0,
+            rules: PollRules::default(),
+            kind: PollKind::Single,
+            status: PollStatus::Open,
+            status_reason: String::new(),
+            creator_id: None,
+            runoff: Vec::new(),
+        };
+
+        insert_poll(&conn, &closed_poll)?;
+
+        let unknown_choice = handle(&conn, "POST", &format!("/polls/{}/votes", poll_id), Some(&token), r#"{"choice": "Maybe"}"#);
+        assert_eq!(unknown_choice.status, 400);
+        assert_eq!(unknown_choice.body["message"], "Invalid Vote. \"Maybe\" doesn't match the options of this poll.");
+
+        let closed = handle(&conn, "POST", &format!("/polls/{}/votes", poll_id), Some(&token), r#"{"choice": "Yes"}"#);
+        assert_eq!(closed.status, 409);
+        assert_eq!(closed.body["error"], "poll_closed");
+        assert_eq!(closed.body["expiration_date"], closed_poll.expiration_date);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_permissions() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let admin = login(&conn, "Ana");
+        let voter = login(&conn, "Bruno");
+
+        let denied = handle(&conn, "POST", "/polls", Some(&voter), r#"{"question": "Lunch?", "duration": "3d"}"#);
+        assert_eq!(denied.status, 403);
+        assert_eq!(denied.body["error"], "permission_denied");
+
+        let created = handle(&conn, "POST", "/polls", Some(&admin), r#"{"question": "Lunch?", "duration": "3d", "options": ["Pizza", "Sushi"]}"#);
+        let poll_id = created.body["id"].as_str().unwrap().to_string();
+
+        let vote = handle(&conn, "POST", &format!("/polls/{}/votes", poll_id), Some(&voter), r#"{"choice": "pizza"}"#);
+        assert_eq!(vote.status, 201);
+
+        let vote_id = vote.body["id"].as_str().unwrap().to_string();
+
+        // Admins can change anyone's vote, but a voter can't delete someone else's poll
+        assert_eq!(handle(&conn, "PATCH", &format!("/votes/{}", vote_id), Some(&admin), r#"{"choice": "2"}"#).status, 200);
+        assert_eq!(handle(&conn, "DELETE", &format!("/polls/{}", poll_id), Some(&voter), "").status, 403);
+
+        // A voter without an account signs up with the invite an admin gave them
+        voter::create_voter(&conn, "Carla".to_string()).expect("Voter to be created");
+        let invite = user::invite_voter(&conn, "Carla").expect("Invite to be made");
+
+        assert_eq!(handle(&conn, "POST", "/users", None, r#"{"name": "Carla", "password": "correct horse"}"#).status, 400);
+
+        let joined = handle(&conn, "POST", "/users", None, &format!(r#"{{"invite": "{}", "password": "correct horse"}}"#, invite));
+        assert_eq!(joined.status, 201);
+        assert_eq!(joined.body["name"], "Carla");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod migrations {
+    use rusqlite::{Connection, Result};
+
+    use crate::create_tables;
+    use crate::ledger;
+    use crate::poll;
+    use crate::poll::PollDuration;
+    use crate::vote;
+    use crate::vote::VoteAction;
+
+    const POLL_ID: &str = "5e4d0880-a8b7-475a-8f28-7b22532db382";
+
+    // The tables and rows of a database made before there were poll options, voters or users
+    fn old_database() -> Result<Connection> {
+        let conn = Connection::open_in_memory()?;
+
+        conn.execute_batch(
+            "
+            CREATE TABLE Poll (
+                 id TEXT PRIMARY KEY,
+                 question TEXT NOT NULL,
+                 poll_duration INTEGER NOT NULL,
+                 create_date DATE NOT NULL,
+                 expiration_date DATE NOT NULL,
+                 positive_votes INTEGER NOT NULL,
+                 negative_votes INTEGER NOT NULL
+                 );
+            CREATE TABLE Vote (
+                 id TEXT PRIMARY KEY,
+                 choice TEXT NOT NULL,
+                 comment TEXT,
+                 voting_power INTEGER NOT NULL,
+                 create_date DATE NOT NULL,
+                 poll_id TEXT NOT NULL REFERENCES Poll(id)
+             );
+            INSERT INTO Poll VALUES ('5e4d0880-a8b7-475a-8f28-7b22532db382', 'Do you like Rust?', 7, 1733016411, 1733621211, 1, 0);
+            INSERT INTO Vote VALUES ('d8c82df5-13cf-43af-84ee-e5c3e74058fa', 'y', '', 1, 1733016470, '5e4d0880-a8b7-475a-8f28-7b22532db382');
+            ",
+        )?;
+
+        Ok(conn)
+    }
+
+    fn user_version(conn: &Connection) -> Result<i64> {
+        conn.pragma_query_value(None, "user_version", |row| row.get(0))
+    }
+
+    #[test]
+    fn test_new_database_needs_no_migration() -> Result<()> {
+        let conn = Connection::open_in_memory()?;
+
+        create_tables(&conn)?;
+
+        let version = user_version(&conn)?;
+        assert!(version > 0);
+
+        // Starting again changes nothing
+        create_tables(&conn)?;
+
+        assert_eq!(user_version(&conn)?, version);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_old_database_is_upgraded() -> Result<()> {
+        let conn = old_database()?;
+
+        create_tables(&conn)?;
+
+        // The counters became a Yes and a No option, and the vote points at Yes
+        let options: Vec<(String, String, i64)> = conn
+            .prepare("SELECT id, label, votes FROM PollOption WHERE poll_id = ?1 ORDER BY position")?
+            .query_map([POLL_ID], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+            .collect::<Result<_>>()?;
+
+        assert_eq!(options.iter().map(|(_, label, votes)| (label.as_str(), *votes)).collect::<Vec<_>>(), vec![("Yes", 1), ("No", 0)]);
+
+        let choice: String = conn.query_row("SELECT choice FROM Vote", [], |row| row.get(0))?;
+        assert_eq!(choice, options[0].0);
+
+        assert!(conn.prepare("SELECT positive_votes FROM Poll").is_err());
+
+        let (create_date, start_date): (i64, i64) = conn.query_row("SELECT create_date, start_date FROM Poll", [], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        assert_eq!(start_date, create_date);
+
+        let abstentions: i64 = conn.query_row("SELECT abstentions FROM Poll", [], |row| row.get(0))?;
+        assert_eq!(abstentions, 0);
+
+        // Votes from before there were voters have none
+        let voter_id: Option<String> = conn.query_row("SELECT voter_id FROM Vote", [], |row| row.get(0))?;
+        assert_eq!(voter_id, None);
+
+        // The vote was logged as cast when it was, so the ledger checks out
+        let events = vote::get_events(&conn)?;
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].action, VoteAction::Cast);
+        assert_eq!(events[0].event_date, 1733016470);
+        assert_eq!(ledger::verify_ledger(&conn)?.problem, None);
+
+        let anonymous: bool = conn.query_row("SELECT anonymous FROM Poll", [], |row| row.get(0))?;
+        assert!(!anonymous);
+
+        let receipt_hash: Option<String> = conn.query_row("SELECT receipt_hash FROM Vote", [], |row| row.get(0))?;
+        assert_eq!(receipt_hash, None);
+
+        // And it all loads like a poll made today
+        let polls = poll::get_polls(&conn)?;
+        assert_eq!(polls[0].poll_duration, PollDuration::Days(7));
+        assert_eq!(polls[0].creator_id, None);
+        assert_eq!(polls[0].options.iter().map(|option| option.votes).collect::<Vec<i64>>(), vec![1, 0]);
+        assert_eq!(vote::get_votes(&conn)?[0].option_label, "Yes");
+        assert!(user_version(&conn)? > 0);
+
+        Ok(())
+    }
+}
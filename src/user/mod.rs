@@ -0,0 +1,342 @@
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use rusqlite::{Connection, OptionalExtension, Result, types::ToSqlOutput, ToSql, types::FromSqlError, types::ValueRef, types::FromSql};
+use std::fmt;
+use std::str::FromStr;
+use chrono::Local;
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+use crate::error::PollError;
+use crate::poll::serialize_date;
+use crate::voter::{self, Voter};
+
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+// How long an API token lasts after logging in
+pub const SESSION_HOURS: i64 = 24;
+
+// What a user may do, checked by the permission module. Admins can do anything, poll managers create polls
+// and manage their own, voters vote and observers only read
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub enum Role {
+    Admin,
+    PollManager,
+    #[default]
+    Voter,
+    Observer,
+}
+
+impl FromStr for Role {
+    type Err = PollError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.trim().to_lowercase().replace(['-', ' '], "_").as_str() {
+            "admin" => Ok(Role::Admin),
+            "poll_manager" => Ok(Role::PollManager),
+            "voter" => Ok(Role::Voter),
+            "observer" => Ok(Role::Observer),
+            _ => Err(PollError::InvalidRole),
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Admin => write!(f, "admin"),
+            Role::PollManager => write!(f, "poll_manager"),
+            Role::Voter => write!(f, "voter"),
+            Role::Observer => write!(f, "observer"),
+        }
+    }
+}
+
+impl FromSql for Role {
+    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value.as_str()?.parse().map_err(|_| FromSqlError::Other("Invalid role".into()))
+    }
+}
+
+impl ToSql for Role {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
+    }
+}
+
+impl Serialize for Role {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+// Someone who can log in. Each user votes through the voter of the same name, which keeps their weight
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct User {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(serialize_with = "serialize_date")]
+    pub create_date: i64,
+    pub voter_id: Uuid,
+    pub role: Role,
+}
+
+const SELECT_USERS: &str = "SELECT id, name, create_date, voter_id, role FROM User";
+
+fn user_from_row(row: &rusqlite::Row) -> Result<User> {
+    Ok(User {
+        id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+        name: row.get(1)?,
+        create_date: row.get(2)?,
+        voter_id: Uuid::parse_str(row.get::<_, String>(3)?.as_str()).unwrap(),
+        role: row.get(4)?,
+    })
+}
+
+pub fn find_user(conn: &Connection, name: &str) -> Result<Option<User>> {
+    conn.query_row(
+        &format!("{} WHERE name = ?1", SELECT_USERS),
+        [name.trim()],
+        user_from_row,
+    ).optional()
+}
+
+pub fn get_users(conn: &Connection) -> Result<Vec<User>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY name", SELECT_USERS))?;
+    let user_iter = stmt.query_map([], user_from_row)?;
+
+    user_iter.collect()
+}
+
+// The voter the user's votes are cast as
+pub fn user_voter(conn: &Connection, user: &User) -> Result<Voter> {
+    match voter::get_voter(conn, &user.voter_id)? {
+        Some(voter) => Ok(voter),
+        None => Err(rusqlite::Error::QueryReturnedNoRows),
+    }
+}
+
+// Argon2 with a random salt, kept together in one PHC string like $argon2id$v=19$...
+fn hash_password(password: &str) -> String {
+    let salt = SaltString::encode_b64(Uuid::new_v4().as_bytes()).expect("A uuid to fit in a salt");
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Password to be hashed")
+        .to_string()
+}
+
+// Every user gets a voter of their own. A name that a voter already has is taken, even without an account,
+// since signing up under it would hand that voter's weight and votes to whoever did it first. That voter
+// signs up with an invite instead.
+// The first user is an admin, so there is someone to hand out roles, and everyone after is a voter
+pub fn create_user(conn: &Connection, name: String, password: &str) -> Result<User, PollError> {
+    if name.trim().is_empty() {
+        return Err(PollError::UserNameEmpty);
+    }
+
+    if name.trim().chars().count() > 50 {
+        return Err(PollError::UserNameTooLong { max: 50 });
+    }
+
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(PollError::PasswordTooShort { min: MIN_PASSWORD_LEN });
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    if find_user(&tx, &name)?.is_some() || voter::find_voter(&tx, &name)?.is_some() {
+        return Err(PollError::UserNameTaken);
+    }
+
+    let voter = voter::create_voter(&tx, name.clone())?;
+    let first_user = get_users(&tx)?.is_empty();
+
+    let user = insert_user(&tx, voter, password, if first_user { Role::Admin } else { Role::Voter })?;
+
+    tx.commit()?;
+
+    Ok(user)
+}
+
+// Gives a voter without an account the code to sign up as them, replacing any code they were given before.
+// Whether the caller may invite is left to the permission module
+pub fn invite_voter(conn: &Connection, name: &str) -> Result<String, PollError> {
+    let tx = conn.unchecked_transaction()?;
+
+    let voter = match voter::find_voter(&tx, name)? {
+        Some(voter) => voter,
+        None => return Err(PollError::VoterNotFound),
+    };
+
+    if has_user(&tx, &voter)? {
+        return Err(PollError::VoterHasUser);
+    }
+
+    let code = Uuid::new_v4().simple().to_string();
+
+    tx.execute(
+        "INSERT OR REPLACE INTO Invite (voter_id, code_hash, create_date) VALUES (?1, ?2, ?3)",
+        (voter.id.to_string(), token_hash(&code), Local::now().timestamp()),
+    )?;
+
+    tx.commit()?;
+
+    Ok(code)
+}
+
+// Signs up as the voter the invite was given to, who keeps their name, weight and votes. The invite can only
+// be used once
+pub fn join(conn: &Connection, code: &str, password: &str) -> Result<User, PollError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(PollError::PasswordTooShort { min: MIN_PASSWORD_LEN });
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    let voter_id: Option<String> = tx.query_row(
+        "SELECT voter_id FROM Invite WHERE code_hash = ?1",
+        [token_hash(code)],
+        |row| row.get(0),
+    ).optional()?;
+
+    let voter = match voter_id {
+        Some(voter_id) => voter::get_voter(&tx, &Uuid::parse_str(&voter_id).unwrap())?,
+        None => None,
+    };
+
+    let voter = match voter {
+        Some(voter) => voter,
+        None => return Err(PollError::InvalidInvite),
+    };
+
+    tx.execute("DELETE FROM Invite WHERE voter_id = ?1", [voter.id.to_string()])?;
+
+    let user = insert_user(&tx, voter, password, Role::Voter)?;
+
+    tx.commit()?;
+
+    Ok(user)
+}
+
+fn has_user(conn: &Connection, voter: &Voter) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM User WHERE voter_id = ?1)",
+        [voter.id.to_string()],
+        |row| row.get(0),
+    )
+}
+
+fn insert_user(conn: &Connection, voter: Voter, password: &str, role: Role) -> Result<User> {
+    let user = User {
+        id: Uuid::new_v4(),
+        name: voter.name,
+        create_date: Local::now().timestamp(),
+        voter_id: voter.id,
+        role,
+    };
+
+    conn.execute(
+        "INSERT INTO User (id, name, password_hash, create_date, voter_id, role) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        (
+            user.id.to_string(),
+            &user.name,
+            hash_password(password),
+            user.create_date,
+            user.voter_id.to_string(),
+            user.role,
+        ),
+    )?;
+
+    Ok(user)
+}
+
+// Whether the caller may change roles is left to the permission module. The last admin can't lose the role,
+// since nobody would be left to give it back
+pub fn set_user_role(conn: &Connection, name: &str, role: Role) -> Result<User, PollError> {
+    let tx = conn.unchecked_transaction()?;
+
+    let mut user = match find_user(&tx, name)? {
+        Some(user) => user,
+        None => return Err(PollError::UserNotFound),
+    };
+
+    let admins: i64 = tx.query_row("SELECT COUNT(*) FROM User WHERE role = ?1", [Role::Admin], |row| row.get(0))?;
+
+    if user.role == Role::Admin && role != Role::Admin && admins == 1 {
+        return Err(PollError::LastAdmin);
+    }
+
+    tx.execute(
+        "UPDATE User SET role = ?1 WHERE id = ?2",
+        (role, user.id.to_string()),
+    )?;
+
+    tx.commit()?;
+
+    user.role = role;
+
+    Ok(user)
+}
+
+// Unknown names and wrong passwords give the same error, so names can't be guessed from it
+pub fn login(conn: &Connection, name: &str, password: &str) -> Result<User, PollError> {
+    let found: Option<(User, String)> = conn.query_row(
+        "SELECT id, name, create_date, voter_id, role, password_hash FROM User WHERE name = ?1",
+        [name.trim()],
+        |row| Ok((user_from_row(row)?, row.get(5)?)),
+    ).optional()?;
+
+    let (user, password_hash) = match found {
+        Some(found) => found,
+        None => return Err(PollError::InvalidLogin),
+    };
+
+    let verified = PasswordHash::new(&password_hash)
+        .map(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+        .unwrap_or(false);
+
+    if verified {
+        Ok(user)
+    } else {
+        Err(PollError::InvalidLogin)
+    }
+}
+
+// Tokens and invites are only kept as a hash, like receipts
+fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.trim()))
+}
+
+// Starts a session for the API and returns its token, which is sent back as "Authorization: Bearer <token>"
+pub fn create_session(conn: &Connection, user: &User) -> Result<String> {
+    let token = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let now = Local::now().timestamp();
+
+    conn.execute(
+        "INSERT INTO Session (token_hash, user_id, create_date, expiration_date) VALUES (?1, ?2, ?3, ?4)",
+        (token_hash(&token), user.id.to_string(), now, now + SESSION_HOURS * 60 * 60),
+    )?;
+
+    Ok(token)
+}
+
+// The user a token belongs to, while the session hasn't expired
+pub fn session_user(conn: &Connection, token: &str) -> Result<Option<User>> {
+    conn.query_row(
+        "SELECT User.id, name, User.create_date, voter_id, role FROM Session JOIN User ON Session.user_id = User.id
+        WHERE token_hash = ?1 AND expiration_date > ?2",
+        (token_hash(token), Local::now().timestamp()),
+        user_from_row,
+    ).optional()
+}
+
+pub fn end_session(conn: &Connection, token: &str) -> Result<()> {
+    conn.execute(
+        "DELETE FROM Session WHERE token_hash = ?1",
+        [token_hash(token)],
+    )?;
+
+    Ok(())
+}
@@ -1,239 +1,855 @@
 
-use rusqlite::{Connection, Result};
+use rusqlite::{Connection, OptionalExtension, Result, types::ToSqlOutput, ToSql, types::FromSqlError, types::ValueRef, types::FromSql};
+use std::fmt;
+use std::str::FromStr;
 use chrono::Local;
+use serde::{Serialize, Serializer};
+use sha2::{Digest, Sha256};
 use uuid::Uuid;
-use std::fmt;
-use std::error::Error;
 
-use crate::poll::Poll;
+use crate::error::PollError;
+use crate::ledger;
+use crate::poll::{serialize_date, short_code, Poll, PollKind, PollStatus};
+use crate::tally::Ballot;
+use crate::voter::Voter;
+
+pub const ABSTAIN_LABEL: &str = "Abstain";
+
+// Shown instead of the voter on votes of anonymous polls
+pub const ANONYMOUS_NAME: &str = "Anonymous";
 
-#[derive(Debug, PartialEq, Clone)]
+// Highest score an option can get on a score poll, the lowest being 0
+pub const MAX_SCORE: i64 = 5;
+
+// What a vote is for. Abstaining counts as taking part in the poll but goes to none of the options
+#[derive(Debug, Clone, PartialEq)]
 pub enum VoteChoice {
-    Yes,
-    No
+    Option(Uuid),
+    Ranked(Vec<Uuid>), // Options of a ranked poll in order of preference
+    Approved(Vec<Uuid>), // Options approved of on an approval poll
+    Scored(Vec<(Uuid, i64)>), // Every option of a score poll with its score
+    Abstain,
+}
+
+impl VoteChoice {
+    // The option kept in the choice column: the only one, or the first of a ballot with several
+    pub fn first_option(&self) -> Option<Uuid> {
+        match self {
+            VoteChoice::Option(option_id) => Some(*option_id),
+            VoteChoice::Ranked(option_ids) | VoteChoice::Approved(option_ids) => option_ids.first().copied(),
+            VoteChoice::Scored(scores) => scores.first().map(|(option_id, _)| *option_id),
+            VoteChoice::Abstain => None,
+        }
+    }
+}
+
+// Exported as the option id, the ids of a ranking separated by >, the approved ids separated by +,
+// each id with its score like id=5 separated by +, or "abstain"
+impl fmt::Display for VoteChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoteChoice::Option(option_id) => write!(f, "{}", option_id),
+            VoteChoice::Ranked(ranking) => {
+                let ids: Vec<String> = ranking.iter().map(|option_id| option_id.to_string()).collect();
+                write!(f, "{}", ids.join(">"))
+            }
+            VoteChoice::Approved(approved) => {
+                let ids: Vec<String> = approved.iter().map(|option_id| option_id.to_string()).collect();
+                write!(f, "{}", ids.join("+"))
+            }
+            VoteChoice::Scored(scores) => {
+                let scores: Vec<String> = scores.iter().map(|(option_id, score)| format!("{}={}", option_id, score)).collect();
+                write!(f, "{}", scores.join("+"))
+            }
+            VoteChoice::Abstain => write!(f, "abstain"),
+        }
+    }
+}
+
+impl FromStr for VoteChoice {
+    type Err = Box<dyn std::error::Error + Send + Sync>;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        if input == "abstain" {
+            Ok(VoteChoice::Abstain)
+        } else if input.contains('>') {
+            Ok(VoteChoice::Ranked(input.split('>').map(Uuid::parse_str).collect::<Result<_, _>>()?))
+        } else if input.contains('=') {
+            let mut scores = Vec::new();
+
+            for part in input.split('+') {
+                let (option_id, score) = part.split_once('=').unwrap_or((part, ""));
+                scores.push((Uuid::parse_str(option_id)?, score.parse()?));
+            }
+
+            Ok(VoteChoice::Scored(scores))
+        } else if input.contains('+') {
+            Ok(VoteChoice::Approved(input.split('+').map(Uuid::parse_str).collect::<Result<_, _>>()?))
+        } else {
+            Ok(VoteChoice::Option(Uuid::parse_str(input)?))
+        }
+    }
+}
+
+impl FromSql for VoteChoice {
+    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        value.as_str()?.parse().map_err(FromSqlError::Other)
+    }
 }
 
-#[derive(Debug, Clone)]
+// The choice column only holds the first option, ballots with several are kept in BallotEntry
+impl ToSql for VoteChoice {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        match self.first_option() {
+            Some(option_id) => Ok(ToSqlOutput::from(option_id.to_string())),
+            None => Ok(ToSqlOutput::from("abstain")),
+        }
+    }
+}
+
+impl Serialize for VoteChoice {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct Vote {
     pub id: Uuid,
-    pub choice:  VoteChoice,
+    pub choice: VoteChoice,
+    pub option_label: String,
     pub comment: String,
-    pub voting_power: i16,
+    pub voting_power: i64, // The voter's weight when the vote was cast
+    #[serde(serialize_with = "serialize_date")]
     pub create_date: i64,
     pub poll_id: Uuid,
     pub poll_question: String,
+    pub voter_id: Option<Uuid>, // None on anonymous polls, where the vote isn't tied to whoever cast it
+    pub voter_name: String,
+    // Only set on the vote create_vote returns, since just a hash of it is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<String>,
 }
-#[derive(Debug)]
-pub struct ValidationError {
-   pub details: String,
+impl Vote {
+    pub fn short_code(&self) -> String {
+        short_code(&self.id)
+    }
+}
+
+// What happened to a vote in one VoteEvent
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum VoteAction {
+    Cast,
+    Changed,
+    Retracted,
 }
 
-impl PartialEq for ValidationError {
-    fn eq(&self, other: &Self) -> bool {
-         self.details == other.details
+impl fmt::Display for VoteAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VoteAction::Cast => write!(f, "cast"),
+            VoteAction::Changed => write!(f, "changed"),
+            VoteAction::Retracted => write!(f, "retracted"),
+        }
     }
- }
+}
 
-impl ValidationError {
-   pub fn new(msg: &str) -> ValidationError {
-        ValidationError {
-            details: msg.to_string(),
+impl FromSql for VoteAction {
+    fn column_result(value: ValueRef<'_>) -> Result<Self, FromSqlError> {
+        match value.as_str()? {
+            "cast" => Ok(VoteAction::Cast),
+            "changed" => Ok(VoteAction::Changed),
+            "retracted" => Ok(VoteAction::Retracted),
+            _ => Err(FromSqlError::Other("Invalid vote action".into())),
         }
     }
 }
 
-impl fmt::Display for ValidationError {
-   fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.details)
+impl ToSql for VoteAction {
+    fn to_sql(&self) -> Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.to_string()))
     }
 }
 
-impl Error for ValidationError {}
+impl Serialize for VoteAction {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
 
-impl fmt::Display for VoteChoice {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-         match self {
-             VoteChoice::Yes => write!(f, "Yes"),
-             VoteChoice::No => write!(f, "No"),
-         }
-     }
- }
+// One entry of the vote audit trail, with the vote as it was before and after. A cast has nothing before
+// and a retraction nothing after. Labels are kept as they were, since options and polls can go away later
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VoteEvent {
+    pub id: Uuid,
+    pub vote_id: Uuid,
+    pub poll_id: Uuid,
+    pub voter_id: Option<Uuid>, // None on anonymous polls, like the vote itself
+    pub voter_name: String,
+    pub action: VoteAction,
+    pub choice: Option<VoteChoice>,
+    pub option_label: Option<String>,
+    pub comment: Option<String>,
+    pub previous_choice: Option<VoteChoice>,
+    pub previous_label: Option<String>,
+    pub previous_comment: Option<String>,
+    pub voting_power: i64,
+    #[serde(serialize_with = "serialize_date")]
+    pub event_date: i64,
+    pub previous_hash: String, // Hash of the entry before, which chains the log together
+    pub hash: String,
+}
+
+// Abstentions have no option to join, so they get the abstain label. Votes of anonymous polls have no voter
+const SELECT_VOTES: &str = "SELECT Vote.id as id, choice, COALESCE(label, 'Abstain'), comment, voting_power, Vote.create_date as create_date, Vote.poll_id as poll_id, question, voter_id, COALESCE(name, 'Anonymous') FROM Vote JOIN Poll ON Vote.poll_id = Poll.id LEFT JOIN PollOption ON Vote.choice = PollOption.id LEFT JOIN Voter ON Vote.voter_id = Voter.id";
+
+fn vote_from_row(row: &rusqlite::Row) -> Result<Vote> {
+    Ok(Vote {
+        id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+        choice: row.get(1)?,
+        option_label: row.get(2)?,
+        comment: row.get(3)?,
+        voting_power: row.get(4)?,
+        create_date: row.get(5)?,
+        poll_id: Uuid::parse_str(row.get::<_, String>(6)?.as_str()).unwrap(),
+        poll_question: row.get(7)?,
+        voter_id: row.get::<_, Option<String>>(8)?.map(|voter_id| Uuid::parse_str(&voter_id).unwrap()),
+        voter_name: row.get(9)?,
+        receipt: None,
+    })
+}
+
+// The ballot a vote on a ranked, approval or score poll keeps in BallotEntry, with a label showing every option
+// in it. Other votes keep none
+fn stored_ballot(conn: &Connection, vote_id: &Uuid) -> Result<Option<(VoteChoice, String)>> {
+    let mut stmt = conn.prepare(
+        "SELECT option_id, label, score, kind FROM BallotEntry JOIN PollOption ON BallotEntry.option_id = PollOption.id
+        JOIN Poll ON PollOption.poll_id = Poll.id WHERE vote_id = ?1 ORDER BY rank"
+    )?;
+    let entries: Vec<(String, String, Option<i64>, PollKind)> = stmt
+        .query_map([vote_id.to_string()], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+        .collect::<Result<_>>()?;
+
+    let kind = match entries.first() {
+        Some((_, _, _, kind)) => *kind,
+        None => return Ok(None),
+    };
+    let option_ids: Vec<Uuid> = entries.iter().map(|(option_id, _, _, _)| Uuid::parse_str(option_id).unwrap()).collect();
+    let labels: Vec<String> = entries.iter().map(|(_, label, _, _)| label.clone()).collect();
+
+    let choice = match kind {
+        PollKind::Single => VoteChoice::Option(option_ids[0]),
+        PollKind::Ranked => VoteChoice::Ranked(option_ids),
+        PollKind::Approval => VoteChoice::Approved(option_ids),
+        PollKind::Score => VoteChoice::Scored(option_ids.into_iter().zip(entries.iter().map(|(_, _, score, _)| score.unwrap_or_default())).collect()),
+    };
+    let label = ballot_label(&choice, &labels);
+
+    Ok(Some((choice, label)))
+}
+
+// Votes with a ballot get all of it back, since the choice column only has its first option
+fn load_ballot(conn: &Connection, vote: &mut Vote) -> Result<()> {
+    if let Some((choice, label)) = stored_ballot(conn, &vote.id)? {
+        vote.choice = choice;
+        vote.option_label = label;
+    }
+
+    Ok(())
+}
 
 pub fn get_votes(conn: &Connection) -> Result<Vec<Vote>>{
-    let mut stmt = conn.prepare("SELECT Vote.id as id, choice, comment, voting_power, Vote.create_date as create_date, poll_id, question FROM Vote JOIN Poll ON Vote.poll_id = Poll.id")?;
-
-    let vote_iter = stmt.query_map([], |row| {
-        Ok(Vote {
-            id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
-            choice: match row.get::<_, String>(1)?.as_str().trim() {
-                "y" => VoteChoice::Yes,
-                "n" => VoteChoice::No,
-                _ => panic!("Invalid Vote"),
-            },
-            comment: row.get(2)?,
-            voting_power: row.get(3)?,
-            create_date: row.get(4)?,
-            poll_id: Uuid::parse_str(row.get::<_, String>(5)?.as_str()).unwrap(),
-            poll_question: row.get(6)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY Vote.rowid", SELECT_VOTES))?;
+
+    let vote_iter = stmt.query_map([], vote_from_row)?;
 
     let mut votes = Vec::new();
 
     for vote in vote_iter {
-        votes.push(vote?);
+        let mut vote = vote?;
+        load_ballot(conn, &mut vote)?;
+        votes.push(vote);
     }
 
     Ok(votes)
 }
 
-pub fn create_vote (conn: &Connection, poll: Poll, vote: &str, comment: String) -> Result<Vote, Box<dyn Error>>{
+// A new receipt, 24 random hex digits in groups of 4. The version and variant bytes of the uuid are left out
+// since they are the same on every uuid
+fn new_receipt() -> String {
+    let bytes = Uuid::new_v4().into_bytes();
+    let digits: String = bytes[..6].iter().chain(&bytes[10..]).map(|byte| format!("{:02x}", byte)).collect();
+
+    digits.as_bytes().chunks(4).map(|group| String::from_utf8_lossy(group)).collect::<Vec<_>>().join("-")
+}
+
+// Dashes, spaces and case are ignored, so a receipt can be typed back however it is written
+fn receipt_digits(receipt: &str) -> String {
+    receipt.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_lowercase()
+}
+
+// What is stored for a receipt on the vote
+fn receipt_hash(receipt: &str) -> String {
+    format!("{:x}", Sha256::digest(receipt_digits(receipt)))
+}
+
+// What is stored for a receipt on the participation it goes with. It is hashed differently from the one on the
+// vote, so the two can't be matched without the receipt itself
+fn participation_hash(receipt: &str) -> String {
+    format!("{:x}", Sha256::digest(format!("participation:{}", receipt_digits(receipt))))
+}
+
+pub fn get_vote_by_receipt(conn: &Connection, receipt: &str) -> Result<Option<Vote>> {
+    let vote_id: Option<String> = conn.query_row(
+        "SELECT id FROM Vote WHERE receipt_hash = ?1",
+        [receipt_hash(receipt)],
+        |row| row.get(0),
+    ).optional()?;
+
+    match vote_id {
+        Some(vote_id) => get_vote(conn, &Uuid::parse_str(&vote_id).unwrap()),
+        None => Ok(None),
+    }
+}
+
+pub fn get_vote(conn: &Connection, id: &Uuid) -> Result<Option<Vote>> {
+    let vote = conn.query_row(
+        &format!("{} WHERE Vote.id = ?1", SELECT_VOTES),
+        [id.to_string()],
+        vote_from_row,
+    ).optional()?;
+
+    match vote {
+        Some(mut vote) => {
+            load_ballot(conn, &mut vote)?;
+            Ok(Some(vote))
+        }
+        None => Ok(None),
+    }
+}
+
+// The rankings cast on a poll, in the order they were cast. Abstentions have no ranking and are left out
+pub fn get_ballots(conn: &Connection, poll_id: &Uuid) -> Result<Vec<Ballot>> {
+    let mut stmt = conn.prepare(
+        "SELECT BallotEntry.vote_id, BallotEntry.option_id, voting_power FROM BallotEntry JOIN Vote ON BallotEntry.vote_id = Vote.id
+        WHERE Vote.poll_id = ?1 ORDER BY Vote.rowid, rank"
+    )?;
+    let entries = stmt.query_map([poll_id.to_string()], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)?)))?;
+
+    let mut ballots: Vec<Ballot> = Vec::new();
+    let mut last_vote_id = String::new();
+
+    for entry in entries {
+        let (vote_id, option_id, weight) = entry?;
+        let option_id = Uuid::parse_str(&option_id).unwrap();
+
+        match ballots.last_mut() {
+            Some(ballot) if vote_id == last_vote_id => ballot.ranking.push(option_id),
+            _ => ballots.push(Ballot { ranking: vec![option_id], weight }),
+        }
+
+        last_vote_id = vote_id;
+    }
+
+    Ok(ballots)
+}
+
+// Returns the label of the option if it belongs to the poll
+fn find_poll_option(conn: &Connection, poll_id: &Uuid, option_id: &Uuid) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT label FROM PollOption WHERE id = ?1 AND poll_id = ?2",
+        [option_id.to_string(), poll_id.to_string()],
+        |row| row.get(0),
+    ).optional()
+}
+
+fn option_label(conn: &Connection, poll_id: &Uuid, option_id: &Uuid) -> Result<String, PollError> {
+    match find_poll_option(conn, poll_id, option_id)? {
+        Some(label) => Ok(label),
+        None => Err(PollError::OptionNotInPoll { poll_id: *poll_id, option_id: *option_id }),
+    }
+}
+
+// The label shown for a choice, given the labels of its options in the same order
+fn ballot_label(choice: &VoteChoice, labels: &[String]) -> String {
+    match choice {
+        VoteChoice::Option(_) => labels.join(""),
+        VoteChoice::Ranked(_) => labels.join(" > "),
+        VoteChoice::Approved(_) => labels.join(", "),
+        VoteChoice::Scored(scores) => labels
+            .iter()
+            .zip(scores)
+            .map(|(label, (_, score))| format!("{} {}", label, score))
+            .collect::<Vec<String>>()
+            .join(", "),
+        VoteChoice::Abstain => ABSTAIN_LABEL.to_string(),
+    }
+}
+
+fn has_duplicates(option_ids: &[Uuid]) -> bool {
+    option_ids.iter().enumerate().any(|(i, option_id)| option_ids[..i].contains(option_id))
+}
+
+// Checks the choice fits the kind of poll and its options belong to it. Returns the choice as it is stored,
+// since a single option on a ranked or approval poll is a ballot of one, and the label shown for it
+fn check_choice(conn: &Connection, poll_id: &Uuid, kind: PollKind, choice: VoteChoice) -> Result<(VoteChoice, String), PollError> {
+    let choice = match (kind, choice) {
+        (_, VoteChoice::Abstain) => VoteChoice::Abstain,
+        (PollKind::Single, VoteChoice::Option(option_id)) => VoteChoice::Option(option_id),
+        (PollKind::Single, VoteChoice::Ranked(option_ids) | VoteChoice::Approved(option_ids)) if option_ids.len() == 1 => VoteChoice::Option(option_ids[0]),
+        (PollKind::Ranked, VoteChoice::Option(option_id)) => VoteChoice::Ranked(vec![option_id]),
+        (PollKind::Ranked, VoteChoice::Ranked(ranking)) => {
+            if ranking.is_empty() || has_duplicates(&ranking) {
+                return Err(PollError::InvalidRanking);
+            }
+
+            VoteChoice::Ranked(ranking)
+        }
+        (PollKind::Approval, VoteChoice::Option(option_id)) => VoteChoice::Approved(vec![option_id]),
+        (PollKind::Approval, VoteChoice::Approved(approved)) => {
+            if approved.is_empty() || has_duplicates(&approved) {
+                return Err(PollError::InvalidApproval);
+            }
+
+            VoteChoice::Approved(approved)
+        }
+        (PollKind::Score, VoteChoice::Scored(scores)) => {
+            let option_count: usize = conn.query_row(
+                "SELECT COUNT(*) FROM PollOption WHERE poll_id = ?1",
+                [poll_id.to_string()],
+                |row| row.get(0),
+            )?;
+            let option_ids: Vec<Uuid> = scores.iter().map(|(option_id, _)| *option_id).collect();
+
+            // Every option once, and each of them belonging to the poll is checked with the labels below
+            if scores.len() != option_count || has_duplicates(&option_ids) || scores.iter().any(|(_, score)| !(0..=MAX_SCORE).contains(score)) {
+                return Err(PollError::InvalidScores { max: MAX_SCORE });
+            }
+
+            VoteChoice::Scored(scores)
+        }
+        (kind, _) => return Err(PollError::BallotNotAllowed(kind)),
+    };
+
+    let option_ids: Vec<Uuid> = match &choice {
+        VoteChoice::Option(option_id) => vec![*option_id],
+        VoteChoice::Ranked(option_ids) | VoteChoice::Approved(option_ids) => option_ids.clone(),
+        VoteChoice::Scored(scores) => scores.iter().map(|(option_id, _)| *option_id).collect(),
+        VoteChoice::Abstain => Vec::new(),
+    };
+    let labels = option_ids.iter().map(|option_id| option_label(conn, poll_id, option_id)).collect::<Result<Vec<String>, PollError>>()?;
+    let label = ballot_label(&choice, &labels);
+
+    Ok((choice, label))
+}
+
+// Replaces the ballot kept for a vote. Choices of a single option keep none
+fn save_ballot(conn: &Connection, vote_id: &Uuid, choice: &VoteChoice) -> Result<()> {
+    conn.execute(
+        "DELETE FROM BallotEntry WHERE vote_id = ?1",
+        [vote_id.to_string()],
+    )?;
+
+    let entries: Vec<(Uuid, Option<i64>)> = match choice {
+        VoteChoice::Ranked(option_ids) | VoteChoice::Approved(option_ids) => option_ids.iter().map(|option_id| (*option_id, None)).collect(),
+        VoteChoice::Scored(scores) => scores.iter().map(|(option_id, score)| (*option_id, Some(*score))).collect(),
+        VoteChoice::Option(_) | VoteChoice::Abstain => Vec::new(),
+    };
+
+    for (rank, (option_id, score)) in entries.iter().enumerate() {
+        conn.execute(
+            "INSERT INTO BallotEntry (vote_id, option_id, rank, score) VALUES (?1, ?2, ?3, ?4)",
+            (vote_id.to_string(), option_id.to_string(), rank as i64 + 1, score),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Options keep their counter on PollOption, abstentions on the Poll itself. A ranking counts for its first
+// preference, an approval ballot for every approved option and a score ballot adds the scores times the amount
+fn add_to_tally(conn: &Connection, poll_id: &Uuid, choice: &VoteChoice, amount: i64) -> Result<()> {
+    let counted: Vec<(Uuid, i64)> = match choice {
+        VoteChoice::Option(option_id) => vec![(*option_id, amount)],
+        VoteChoice::Ranked(ranking) => ranking.first().map(|option_id| (*option_id, amount)).into_iter().collect(),
+        VoteChoice::Approved(approved) => approved.iter().map(|option_id| (*option_id, amount)).collect(),
+        VoteChoice::Scored(scores) => scores.iter().map(|(option_id, score)| (*option_id, score * amount)).collect(),
+        VoteChoice::Abstain => {
+            conn.execute(
+                "UPDATE Poll SET abstentions = abstentions + ?1 WHERE id = ?2",
+                (amount, poll_id.to_string()),
+            )?;
+
+            return Ok(());
+        }
+    };
+
+    for (option_id, votes) in counted {
+        conn.execute(
+            "UPDATE PollOption SET votes = votes + ?1 WHERE id = ?2",
+            (votes, option_id.to_string()),
+        )?;
+    }
+
+    Ok(())
+}
+
+// Appends to the VoteEvent log, chained to the last entry by its hash. Entries are never updated or deleted,
+// not even with their vote or poll
+fn log_event(conn: &Connection, action: VoteAction, vote: Option<&Vote>, previous: Option<&Vote>) -> Result<()> {
+    let subject = match vote.or(previous) {
+        Some(subject) => subject,
+        None => return Ok(()),
+    };
+
+    let mut event = VoteEvent {
+        id: Uuid::new_v4(),
+        vote_id: subject.id,
+        poll_id: subject.poll_id,
+        voter_id: subject.voter_id,
+        voter_name: subject.voter_name.clone(),
+        action,
+        choice: vote.map(|vote| vote.choice.clone()),
+        option_label: vote.map(|vote| vote.option_label.clone()),
+        comment: vote.map(|vote| vote.comment.clone()),
+        previous_choice: previous.map(|previous| previous.choice.clone()),
+        previous_label: previous.map(|previous| previous.option_label.clone()),
+        previous_comment: previous.map(|previous| previous.comment.clone()),
+        voting_power: subject.voting_power,
+        event_date: Local::now().timestamp(),
+        previous_hash: ledger::last_hash(conn)?,
+        hash: String::new(),
+    };
+    event.hash = ledger::event_hash(&event);
+
+    conn.execute(
+        "INSERT INTO VoteEvent (id, vote_id, poll_id, voter_id, action, choice, option_label, comment, previous_choice, previous_label, previous_comment, voting_power, event_date, previous_hash, hash)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+        rusqlite::params![
+            event.id.to_string(),
+            event.vote_id.to_string(),
+            event.poll_id.to_string(),
+            event.voter_id.map(|voter_id| voter_id.to_string()),
+            event.action,
+            event.choice.as_ref().map(|choice| choice.to_string()),
+            event.option_label,
+            event.comment,
+            event.previous_choice.as_ref().map(|choice| choice.to_string()),
+            event.previous_label,
+            event.previous_comment,
+            event.voting_power,
+            event.event_date,
+            event.previous_hash,
+            event.hash,
+        ],
+    )?;
+
+    Ok(())
+}
+
+// Logs every vote of the poll as retracted, for deleting the poll along with them
+pub(crate) fn retract_poll_votes(conn: &Connection, poll_id: &Uuid) -> Result<()> {
+    let mut stmt = conn.prepare(&format!("{} WHERE Vote.poll_id = ?1 ORDER BY Vote.rowid", SELECT_VOTES))?;
+    let votes = stmt.query_map([poll_id.to_string()], vote_from_row)?.collect::<Result<Vec<Vote>>>()?;
+
+    for mut vote in votes {
+        load_ballot(conn, &mut vote)?;
+        log_event(conn, VoteAction::Retracted, None, Some(&vote))?;
+    }
+
+    Ok(())
+}
+
+// The event log keeps whole ballots as text, which the choice column can't hold
+fn choice_column(row: &rusqlite::Row, index: usize) -> Result<Option<VoteChoice>> {
+    match row.get::<_, Option<String>>(index)? {
+        Some(choice) => choice.parse().map(Some).map_err(|err| rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, err)),
+        None => Ok(None),
+    }
+}
+
+const SELECT_EVENTS: &str = "SELECT VoteEvent.id, vote_id, poll_id, voter_id, COALESCE(name, 'Anonymous'), action, choice, option_label, comment, previous_choice, previous_label, previous_comment, voting_power, event_date, previous_hash, hash FROM VoteEvent LEFT JOIN Voter ON VoteEvent.voter_id = Voter.id";
+
+fn event_from_row(row: &rusqlite::Row) -> Result<VoteEvent> {
+    Ok(VoteEvent {
+        id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+        vote_id: Uuid::parse_str(row.get::<_, String>(1)?.as_str()).unwrap(),
+        poll_id: Uuid::parse_str(row.get::<_, String>(2)?.as_str()).unwrap(),
+        voter_id: row.get::<_, Option<String>>(3)?.map(|voter_id| Uuid::parse_str(&voter_id).unwrap()),
+        voter_name: row.get(4)?,
+        action: row.get(5)?,
+        choice: choice_column(row, 6)?,
+        option_label: row.get(7)?,
+        comment: row.get(8)?,
+        previous_choice: choice_column(row, 9)?,
+        previous_label: row.get(10)?,
+        previous_comment: row.get(11)?,
+        voting_power: row.get(12)?,
+        event_date: row.get(13)?,
+        previous_hash: row.get(14)?,
+        hash: row.get(15)?,
+    })
+}
+
+// The whole log in the order it was written, which is the order of the hash chain
+pub fn get_events(conn: &Connection) -> Result<Vec<VoteEvent>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY VoteEvent.rowid", SELECT_EVENTS))?;
+    let event_iter = stmt.query_map([], event_from_row)?;
+
+    event_iter.collect()
+}
+
+// The audit trail of a poll, oldest first. It outlives the poll, so a deleted poll can still be looked up by id
+pub fn get_poll_events(conn: &Connection, poll_id: &Uuid) -> Result<Vec<VoteEvent>> {
+    let mut stmt = conn.prepare(&format!("{} WHERE VoteEvent.poll_id = ?1 ORDER BY VoteEvent.rowid", SELECT_EVENTS))?;
+    let event_iter = stmt.query_map([poll_id.to_string()], event_from_row)?;
+
+    event_iter.collect()
+}
+
+// Everything a voter did on every poll, oldest first
+pub fn get_voter_events(conn: &Connection, voter_id: &Uuid) -> Result<Vec<VoteEvent>> {
+    let mut stmt = conn.prepare(&format!("{} WHERE VoteEvent.voter_id = ?1 ORDER BY VoteEvent.rowid", SELECT_EVENTS))?;
+    let event_iter = stmt.query_map([voter_id.to_string()], event_from_row)?;
+
+    event_iter.collect()
+}
+
+// Reads the poll status and dates from the database, so a stale Poll can't be used to vote outside of them.
+// Returns the kind of poll, which the choice is checked against, and whether its votes are anonymous
+fn ensure_poll_open(conn: &Connection, poll_id: &Uuid) -> Result<(PollKind, bool), PollError> {
+    let poll: Option<(i64, i64, PollKind, PollStatus, bool)> = conn.query_row(
+        "SELECT start_date, expiration_date, kind, status, anonymous FROM Poll WHERE id = ?1",
+        [poll_id.to_string()],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    ).optional()?;
+
+    let now = Local::now().timestamp();
+
+    match poll {
+        Some((_, _, _, status, _)) if status != PollStatus::Open => Err(PollError::PollNotOpen {
+            poll_id: *poll_id,
+            status,
+        }),
+        Some((start_date, _, _, _, _)) if now < start_date => Err(PollError::PollNotStarted {
+            poll_id: *poll_id,
+            start_date,
+        }),
+        Some((_, expiration_date, _, _, _)) if now >= expiration_date => Err(PollError::PollClosed {
+            poll_id: *poll_id,
+            expiration_date,
+        }),
+        Some((_, _, kind, _, anonymous)) => Ok((kind, anonymous)),
+        None => Err(PollError::PollNotFound(poll_id.to_string())),
+    }
+}
+
+// Votes of anonymous polls have no voter, so Participation is what records who took part in them
+pub fn has_voted(conn: &Connection, poll_id: &Uuid, voter_id: &Uuid) -> Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM Vote WHERE poll_id = ?1 AND voter_id = ?2)
+        OR EXISTS(SELECT 1 FROM Participation WHERE poll_id = ?1 AND voter_id = ?2)",
+        [poll_id.to_string(), voter_id.to_string()],
+        |row| row.get(0),
+    )
+}
+
+pub fn create_vote (conn: &Connection, poll: Poll, voter: &Voter, choice: VoteChoice, comment: String) -> Result<Vote, PollError>{
+    // The checks, the Vote row and the option counter are all part of the same transaction
+    let tx = conn.unchecked_transaction()?;
+
+    let (kind, anonymous) = ensure_poll_open(&tx, &poll.id)?;
+
+    if has_voted(&tx, &poll.id, &voter.id)? {
+        return Err(PollError::AlreadyVoted { poll_id: poll.id, voter_id: voter.id });
+    }
+
+    let (choice, option_label) = check_choice(&tx, &poll.id, kind, choice)?;
+
     let vote = Vote {
         id: Uuid::new_v4(),
-        choice: match vote.trim() {
-            "y" => VoteChoice::Yes,
-            "n" => VoteChoice::No,
-            _ => {
-                println!("Invalid Vote");
-                return Err(Box::new(ValidationError::new(
-                    "Invalid Vote."
-                )));
-            }
-        },
+        choice,
+        option_label,
         comment: comment.trim().to_string(),
-        voting_power: 1,
+        // A weight only a few voters have would show whose vote it is
+        voting_power: if anonymous { 1 } else { voter.weight },
         create_date: Local::now().timestamp(),
         poll_id: poll.id,
         poll_question: poll.question,
+        voter_id: if anonymous { None } else { Some(voter.id) },
+        voter_name: if anonymous { ANONYMOUS_NAME.to_string() } else { voter.name.clone() },
+        receipt: Some(new_receipt()),
     };
 
     if comment.len() > 100 {
-        return Err(Box::new(ValidationError::new(
-            "Comment is too long. Comment only can have up to 100 chars.",
-         )));
+        return Err(PollError::CommentTooLong { max: 100 });
     }
 
-    let choice = match &vote.choice {
-        VoteChoice::Yes => "y".to_string(),
-        VoteChoice::No => "n".to_string(),
-    };
-
-    conn.execute(
-        "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id) 
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        [
-            &vote.id.to_string(),
-            &choice,
+    tx.execute(
+        "INSERT INTO Vote (id, choice, comment, voting_power, create_date, poll_id, voter_id, receipt_hash) 
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (
+            vote.id.to_string(),
+            &vote.choice,
             &vote.comment,
-            &vote.voting_power.to_string(),
-            &vote.create_date.to_string(),
-            &vote.poll_id.to_string(),
-        ]
+            vote.voting_power,
+            vote.create_date,
+            vote.poll_id.to_string(),
+            vote.voter_id.map(|voter_id| voter_id.to_string()),
+            vote.receipt.as_deref().map(receipt_hash),
+        )
     )?;
 
-    match vote.choice {
-        VoteChoice::Yes => {
-            conn.execute(
-                "UPDATE Poll SET positive_votes = positive_votes + 1 WHERE id = ?1",
-                [&vote.poll_id.to_string()],
-            )?;
-        }
-        VoteChoice::No => {
-            conn.execute(
-                "UPDATE Poll SET negative_votes = negative_votes + 1 WHERE id = ?1",
-                [&vote.poll_id.to_string()],
-            )?;
-        }
+    if anonymous {
+        tx.execute(
+            "INSERT INTO Participation (poll_id, voter_id, ballot_hash) VALUES (?1, ?2, ?3)",
+            (poll.id.to_string(), voter.id.to_string(), vote.receipt.as_deref().map(participation_hash)),
+        )?;
     }
 
-    println!("\nYour vote was registered successfully!");
+    save_ballot(&tx, &vote.id, &vote.choice)?;
+    add_to_tally(&tx, &vote.poll_id, &vote.choice, vote.voting_power)?;
+    log_event(&tx, VoteAction::Cast, Some(&vote), None)?;
+
+    tx.commit()?;
 
     Ok(vote)
 }
 
 pub fn edit_vote(
     conn: &Connection,
-    current_vote: &Vote,
     selected_vote: &Vote,
-    new_choice: String,
+    new_choice: VoteChoice,
     new_comment: String
-) -> Result<Vote, Box<dyn Error>> {
+) -> Result<Vote, PollError> {
+    update_vote(conn, selected_vote, new_choice, new_comment, false)
+}
+
+// Changes the vote the receipt was handed out for, which also works on anonymous polls
+pub fn edit_vote_by_receipt(conn: &Connection, receipt: &str, new_choice: VoteChoice, new_comment: String) -> Result<Vote, PollError> {
+    match get_vote_by_receipt(conn, receipt)? {
+        Some(selected_vote) => update_vote(conn, &selected_vote, new_choice, new_comment, true),
+        None => Err(PollError::ReceiptNotFound),
+    }
+}
+
+fn update_vote(
+    conn: &Connection,
+    selected_vote: &Vote,
+    new_choice: VoteChoice,
+    new_comment: String,
+    with_receipt: bool
+) -> Result<Vote, PollError> {
     if new_comment.len() > 100 {
-        return Err(Box::new(ValidationError::new(
-            "Comment is too long. Comment only can have up to 100 chars.",
-         )));
+        return Err(PollError::CommentTooLong { max: 100 });
     }
 
-    if match  current_vote.choice {
-        VoteChoice::Yes => "y",
-        VoteChoice::No => "n",
-    } == "y" && new_choice.trim() == "n" {
-        conn.execute(
-            "UPDATE Poll SET positive_votes = positive_votes - 1 WHERE id = ?1",
-            [&selected_vote.poll_id.to_string()],
-        )?;
+    let tx = conn.unchecked_transaction()?;
 
-        conn.execute(
-            "UPDATE Poll SET negative_votes = negative_votes + 1 WHERE id = ?1",
-            [&selected_vote.poll_id.to_string()],
-        )?;
-    } else if match  current_vote.choice {
-        VoteChoice::Yes => "y",
-        VoteChoice::No => "n",
-    } == "n" && new_choice.trim() == "y" {
-        conn.execute(
-            "UPDATE Poll SET negative_votes = negative_votes - 1 WHERE id = ?1",
-            [&selected_vote.poll_id.to_string()],
-        )?;
+    let (kind, _) = ensure_poll_open(&tx, &selected_vote.poll_id)?;
 
-        conn.execute(
-            "UPDATE Poll SET positive_votes = positive_votes + 1 WHERE id = ?1",
-            [&selected_vote.poll_id.to_string()],
-        )?;
+    let (new_choice, new_option_label) = check_choice(&tx, &selected_vote.poll_id, kind, new_choice)?;
+
+    // The counters and the log follow what is stored, even if selected_vote was read before another edit
+    let stored_vote = match get_vote(&tx, &selected_vote.id)? {
+        Some(stored_vote) => stored_vote,
+        None => {
+            return Err(PollError::VoteNotFound(selected_vote.id));
+        }
+    };
+
+    // Only the receipt shows an anonymous vote is yours, so nobody gets to change it without one
+    if stored_vote.voter_id.is_none() && !with_receipt {
+        return Err(PollError::AnonymousVote(selected_vote.id));
     }
 
+    add_to_tally(&tx, &selected_vote.poll_id, &stored_vote.choice, -stored_vote.voting_power)?;
+    add_to_tally(&tx, &selected_vote.poll_id, &new_choice, stored_vote.voting_power)?;
+
+    save_ballot(&tx, &selected_vote.id, &new_choice)?;
+
+    let mut edited_vote = stored_vote.clone();
+    edited_vote.option_label = new_option_label;
+
     if new_comment.trim() == "" {
-        conn.execute(
+        tx.execute(
             "UPDATE Vote SET choice = ?1 WHERE id = ?2",
-            [new_choice.trim(), selected_vote.id.to_string().as_str()],
+            (&new_choice, selected_vote.id.to_string()),
         )?;
     } else {
-        conn.execute(
+        tx.execute(
             "UPDATE Vote SET choice = ?1, comment = ?2 WHERE id = ?3",
-            [new_choice.trim(), new_comment.trim(), selected_vote.id.to_string().as_str()],
+            (&new_choice, new_comment.trim(), selected_vote.id.to_string()),
         )?;
+        edited_vote.comment = new_comment.trim().to_string();
     }
 
-    println!("\nYour vote was edited successfully!");
+    edited_vote.choice = new_choice;
 
-    Ok(selected_vote.clone())
+    log_event(&tx, VoteAction::Changed, Some(&edited_vote), Some(&stored_vote))?;
+
+    tx.commit()?;
+
+    Ok(edited_vote)
 }
 
 pub fn delete_vote(
     conn: &Connection,
     selected_vote: &Vote
-) -> Result<Vote> {
-    conn.execute(
+) -> Result<Vote, PollError> {
+    remove_vote(conn, selected_vote, None)
+}
+
+// Retracts the vote the receipt was handed out for, which also works on anonymous polls. Whoever cast it can
+// then vote on the poll again, whoever retracts it
+pub fn delete_vote_by_receipt(conn: &Connection, receipt: &str) -> Result<Vote, PollError> {
+    match get_vote_by_receipt(conn, receipt)? {
+        Some(selected_vote) => remove_vote(conn, &selected_vote, Some(receipt)),
+        None => Err(PollError::ReceiptNotFound),
+    }
+}
+
+fn remove_vote(
+    conn: &Connection,
+    selected_vote: &Vote,
+    receipt: Option<&str>
+) -> Result<Vote, PollError> {
+    let tx = conn.unchecked_transaction()?;
+
+    ensure_poll_open(&tx, &selected_vote.poll_id)?;
+
+    let stored_vote = match get_vote(&tx, &selected_vote.id)? {
+        Some(stored_vote) => stored_vote,
+        None => {
+            return Err(PollError::VoteNotFound(selected_vote.id));
+        }
+    };
+
+    // Only the receipt shows an anonymous vote is yours, so nobody gets to change it without one
+    if stored_vote.voter_id.is_none() {
+        match receipt {
+            // Participation is all that says they voted, and it would keep them from voting again
+            Some(receipt) => {
+                tx.execute(
+                    "DELETE FROM Participation WHERE poll_id = ?1 AND ballot_hash = ?2",
+                    (selected_vote.poll_id.to_string(), participation_hash(receipt)),
+                )?;
+            }
+            None => return Err(PollError::AnonymousVote(selected_vote.id)),
+        }
+    }
+
+    save_ballot(&tx, &selected_vote.id, &VoteChoice::Abstain)?;
+
+    tx.execute(
         "DELETE FROM Vote WHERE id = ?1",
         [selected_vote.id.to_string().as_str()],
     )?;
 
-    if match selected_vote.choice {
-        VoteChoice::Yes => "y",
-        VoteChoice::No => "n",
-    } == "y" {
-        conn.execute(
-            "UPDATE Poll SET positive_votes = positive_votes - 1 WHERE id = ?1",
-            [selected_vote.poll_id.to_string().as_str()],
-        )?;
-    } else {
-        conn.execute(
-            "UPDATE Poll SET negative_votes = negative_votes - 1 WHERE id = ?1",
-            [selected_vote.poll_id.to_string().as_str()],
-        )?;
-    }
+    add_to_tally(&tx, &selected_vote.poll_id, &stored_vote.choice, -stored_vote.voting_power)?;
+    log_event(&tx, VoteAction::Retracted, None, Some(&stored_vote))?;
+
+    tx.commit()?;
 
-    println!("\nYour vote was removed successfully!");
-    
     Ok(selected_vote.clone())
-}
\ No newline at end of file
+}
@@ -0,0 +1,108 @@
+use rusqlite::{Connection, OptionalExtension, Result};
+use chrono::Local;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::PollError;
+use crate::poll::serialize_date;
+
+#[derive(Debug, PartialEq, Clone, Serialize)]
+pub struct Voter {
+    pub id: Uuid,
+    pub name: String,
+    #[serde(serialize_with = "serialize_date")]
+    pub create_date: i64,
+    pub weight: i64, // Voting power of each vote this voter casts, 1 unless set
+}
+
+// Names are unique, so they are enough to identify who is voting
+pub fn find_voter(conn: &Connection, name: &str) -> Result<Option<Voter>> {
+    conn.query_row(
+        "SELECT id, name, create_date, weight FROM Voter WHERE name = ?1",
+        [name.trim()],
+        voter_from_row,
+    ).optional()
+}
+
+pub fn get_voter(conn: &Connection, id: &Uuid) -> Result<Option<Voter>> {
+    conn.query_row(
+        "SELECT id, name, create_date, weight FROM Voter WHERE id = ?1",
+        [id.to_string()],
+        voter_from_row,
+    ).optional()
+}
+
+fn voter_from_row(row: &rusqlite::Row) -> Result<Voter> {
+    Ok(Voter {
+        id: Uuid::parse_str(row.get::<_, String>(0)?.as_str()).unwrap(),
+        name: row.get(1)?,
+        create_date: row.get(2)?,
+        weight: row.get(3)?,
+    })
+}
+
+pub fn get_voters(conn: &Connection) -> Result<Vec<Voter>> {
+    let mut stmt = conn.prepare("SELECT id, name, create_date, weight FROM Voter ORDER BY name")?;
+    let voter_iter = stmt.query_map([], voter_from_row)?;
+
+    voter_iter.collect()
+}
+
+pub fn create_voter(conn: &Connection, name: String) -> Result<Voter, PollError> {
+    if name.trim().is_empty() {
+        return Err(PollError::VoterNameEmpty);
+    }
+
+    if name.trim().chars().count() > 50 {
+        return Err(PollError::VoterNameTooLong { max: 50 });
+    }
+
+    if find_voter(conn, &name)?.is_some() {
+        return Err(PollError::VoterNameTaken);
+    }
+
+    let voter = Voter {
+        id: Uuid::new_v4(),
+        name: name.trim().to_string(),
+        create_date: Local::now().timestamp(),
+        weight: 1,
+    };
+
+    conn.execute(
+        "INSERT INTO Voter (id, name, create_date, weight) VALUES (?1, ?2, ?3, ?4)",
+        (
+            voter.id.to_string(),
+            &voter.name,
+            voter.create_date,
+            voter.weight,
+        ),
+    )?;
+
+    Ok(voter)
+}
+
+// Only votes cast after the change use the new weight, the ones already cast keep theirs. Voters come from
+// signing up, so a name nobody signed up with isn't made into one here, which would take the name from them
+pub fn set_voter_weight(conn: &Connection, name: String, weight: i64) -> Result<Voter, PollError> {
+    if weight < 1 {
+        return Err(PollError::InvalidWeight);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+
+    let mut voter = match find_voter(&tx, &name)? {
+        Some(voter) => voter,
+        None => return Err(PollError::VoterNotFound),
+    };
+
+    tx.execute(
+        "UPDATE Voter SET weight = ?1 WHERE id = ?2",
+        (weight, voter.id.to_string()),
+    )?;
+
+    tx.commit()?;
+
+    voter.weight = weight;
+
+    Ok(voter)
+}